@@ -1,7 +1,7 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use log_parser::{
-    AsyncBufReaderSummarizer, BufReaderSummarizer, CharParser, JsonParser, RegexParser,
-    StringParser, Summarizer,
+    AsyncBufReaderSummarizer, BufReaderSummarizer, CharParser, JsonParser, Parser,
+    RayonMmapSummarizer, RegexParser, StringParser, Summarizer, DEFAULT_GROUP_KEY,
 };
 
 use std::time::Duration;
@@ -10,35 +10,60 @@ fn criterion_benchmark(c: &mut Criterion) {
     c.bench_function("json_parser", |b| {
         b.iter(|| {
             BufReaderSummarizer::default()
-                .summarize::<JsonParser>(black_box("./example_data.log"))
+                .summarize(
+                    black_box("./example_data.log"),
+                    JsonParser::new(DEFAULT_GROUP_KEY),
+                )
                 .unwrap()
         })
     });
     c.bench_function("regex_parser", |b| {
         b.iter(|| {
             BufReaderSummarizer::default()
-                .summarize::<RegexParser>(black_box("./example_data.log"))
+                .summarize(
+                    black_box("./example_data.log"),
+                    RegexParser::new(DEFAULT_GROUP_KEY),
+                )
                 .unwrap()
         })
     });
     c.bench_function("string_parser", |b| {
         b.iter(|| {
             BufReaderSummarizer::default()
-                .summarize::<StringParser>(black_box("./example_data.log"))
+                .summarize(
+                    black_box("./example_data.log"),
+                    StringParser::new(DEFAULT_GROUP_KEY),
+                )
                 .unwrap()
         })
     });
     c.bench_function("char_parser", |b| {
         b.iter(|| {
             BufReaderSummarizer::default()
-                .summarize::<CharParser>(black_box("./example_data.log"))
+                .summarize(
+                    black_box("./example_data.log"),
+                    CharParser::new(DEFAULT_GROUP_KEY),
+                )
                 .unwrap()
         })
     });
     c.bench_function("async_summarizer", |b| {
         b.iter(|| {
             AsyncBufReaderSummarizer::default()
-                .summarize::<StringParser>(black_box("./example_data.log"))
+                .summarize(
+                    black_box("./example_data.log"),
+                    StringParser::new(DEFAULT_GROUP_KEY),
+                )
+                .unwrap()
+        })
+    });
+    c.bench_function("rayon_summarizer", |b| {
+        b.iter(|| {
+            RayonMmapSummarizer::default()
+                .summarize(
+                    black_box("./example_data.log"),
+                    StringParser::new(DEFAULT_GROUP_KEY),
+                )
                 .unwrap()
         })
     });
@@ -0,0 +1,151 @@
+use crate::{ErrorSummary, Summary};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read};
+use std::path::Path;
+
+/// How much of the start of a file is hashed to tell whether it's been
+/// replaced by a different file
+pub const PREFIX_HASH_LENGTH: u64 = 4096;
+
+/// Enough about a file to tell whether it's the same one we read last time,
+/// possibly with more appended to it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileIdentity {
+    pub path: String,
+    /// Not available on every platform
+    pub inode: Option<u64>,
+    /// The size of the file when it was read
+    pub size: u64,
+    /// The number of bytes covered by `prefix_hash`. Shorter than
+    /// [PREFIX_HASH_LENGTH] if the file was.
+    pub prefix_length: u64,
+    pub prefix_hash: u64,
+}
+
+impl FileIdentity {
+    pub fn of(path: &str) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let meta = file.metadata()?;
+        let prefix_length = meta.len().min(PREFIX_HASH_LENGTH);
+        Ok(FileIdentity {
+            path: path.to_owned(),
+            inode: inode(&meta),
+            size: meta.len(),
+            prefix_length,
+            prefix_hash: hash_prefix(file, prefix_length)?,
+        })
+    }
+
+    /// Whether `current` could be this file with more data appended. The
+    /// start of the file is hashed again, as its inode might have been
+    /// reused.
+    pub fn is_prefix_of(&self, current: &FileIdentity) -> io::Result<bool> {
+        if self.path != current.path || self.inode != current.inode || self.size > current.size {
+            return Ok(false);
+        }
+        let hash = hash_prefix(File::open(&current.path)?, self.prefix_length)?;
+        Ok(hash == self.prefix_hash)
+    }
+}
+
+#[cfg(unix)]
+fn inode(meta: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(meta.ino())
+}
+
+#[cfg(not(unix))]
+fn inode(_: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// 64 bit FNV-1a of the first `length` bytes. The std hashers aren't
+/// guaranteed to be stable between releases, and this is written to disk.
+fn hash_prefix<R: Read>(reader: R, length: u64) -> io::Result<u64> {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in BufReader::new(reader).take(length).bytes() {
+        hash ^= byte? as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    Ok(hash)
+}
+
+/// How far through a single input we got
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct InputCheckpoint<E: ErrorSummary> {
+    pub identity: FileIdentity,
+    /// The byte offset of the end of the last whole line read
+    pub offset: u64,
+    /// The number of whole lines read
+    pub lines: usize,
+    /// Everything up to `offset`
+    pub summary: Summary<E>,
+}
+
+/// Progress through a set of inputs, saved between runs so they can be
+/// resumed rather than read from scratch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Checkpoint<E: ErrorSummary> {
+    pub inputs: Vec<InputCheckpoint<E>>,
+}
+
+impl<E: ErrorSummary> Default for Checkpoint<E> {
+    fn default() -> Self {
+        Self { inputs: vec![] }
+    }
+}
+
+impl<E: ErrorSummary> Checkpoint<E> {
+    /// Read a state file, or start afresh if there isn't one yet
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        match File::open(path) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file)).map_err(io::Error::from),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Write the state file. It's written alongside and moved in to place,
+    /// so a crash never leaves half a checkpoint behind.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let writer = BufWriter::new(File::create(&temp)?);
+        serde_json::to_writer(writer, self)?;
+        std::fs::rename(&temp, path)
+    }
+
+    pub fn input(&self, path: &str) -> Option<&InputCheckpoint<E>> {
+        self.inputs.iter().find(|input| input.identity.path == path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_identity_prefix() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("log_parser_identity_{}", std::process::id()));
+        let path_str = path.to_string_lossy().into_owned();
+        std::fs::write(&path, "first\n")?;
+        let before = FileIdentity::of(&path_str)?;
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)?
+            .write_all(b"second\n")?;
+        let after = FileIdentity::of(&path_str)?;
+        assert!(before.is_prefix_of(&after)?);
+        assert!(!after.is_prefix_of(&before)?);
+        // same length, different contents
+        std::fs::write(&path, "FIRST\nsecond\n")?;
+        assert!(!before.is_prefix_of(&FileIdentity::of(&path_str)?)?);
+        std::fs::remove_file(path)
+    }
+}
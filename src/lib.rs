@@ -1,17 +1,40 @@
+pub use checkpoint::{Checkpoint, FileIdentity, InputCheckpoint, PREFIX_HASH_LENGTH};
 pub use parsers::CharParser;
+pub use parsers::DistinctParser;
+pub use parsers::DocumentParser;
 pub use parsers::JsonParser;
-pub use parsers::LogLineMetadata;
 pub use parsers::Parser;
 pub use parsers::RegexParser;
 pub use parsers::StringParser;
+pub use parsers::{
+    format_timestamp, parse_timestamp, TimeWindow, TimestampParser, DEFAULT_TIMESTAMP_FIELDS,
+};
+pub use parsers::{BoxedParser, BoxedParserError};
+pub use parsers::{CompositeParser, CompositeParserError};
+pub use parsers::{Filter, FilterError, FilterParser};
+pub use parsers::{JsonPath, JsonPathError, PathSegment};
+pub use parsers::{LineFilter, LogLineMetadata};
+pub use parsers::{TypeFilter, TypeFilterParser, TypePattern};
+pub use parsers::{DEFAULT_GROUP_KEY, KEY_SEPARATOR};
 pub use summarizers::{
-    AsyncBufReaderSummarizer, AsyncBufReaderSummarizerError, BufReaderSummarizer,
-    DEFAULT_BATCH_SIZE, DEFAULT_BUFFER_CAPACITY, DEFAULT_DELIMITER, DEFAULT_REDUCER_CHANNEL_SIZE,
+    expand_logfiles, AsyncBufReaderSummarizer, AsyncBufReaderSummarizerError, BufReaderSummarizer,
+    RayonMmapSummarizer, RejectFile, SummaryOptions, DEFAULT_BATCH_SIZE, DEFAULT_BUFFER_CAPACITY,
+    DEFAULT_CHUNK_SIZE, DEFAULT_DELIMITER, DEFAULT_FOLLOW_INTERVAL_MS, DEFAULT_MAX_LINE_LENGTH,
+    DEFAULT_REDUCER_CHANNEL_SIZE, STDIN_PATH,
+};
+pub use summary::{
+    BucketStats, DiffStatus, ErrorSummary, FieldSchema, GroupLayout, HyperLogLog, JsonBucket,
+    JsonBucketGroup, JsonDistinct, JsonFileSummary, JsonFilesSummary, JsonGroup, JsonGroupDiff,
+    JsonNestedGroup, JsonSamples, JsonSchemaGroup, JsonSummary, JsonSummaryDiff, JsonTopKey,
+    JsonTopKeys, JsonTypeSize, JsonTypeStats, PrintOptions, Sampling, SavedErrors, SizeSketch,
+    Summary, SummaryFile, TimeBucket, TopBy, TopK, TypeStats, ValueKind, HLL_RELATIVE_ERROR,
+    SKETCH_RELATIVE_ACCURACY, SUMMARY_FORMAT_VERSION,
 };
-pub use summary::{ErrorSummary, JsonSummary, Summary};
 
 pub use crate::summarizers::Summarizer;
 
+/// Saving progress through inputs between runs.
+mod checkpoint;
 mod error;
 /// Convert a byte array to parsed metadata.
 mod parsers;
@@ -1,9 +1,18 @@
 use clap::{Parser, Subcommand};
 use log_parser::{
-    AsyncBufReaderSummarizer, BufReaderSummarizer, LogParserError, MainParser, Summarizer,
-    DEFAULT_BATCH_SIZE, DEFAULT_BUFFER_CAPACITY, DEFAULT_DELIMITER, DEFAULT_REDUCER_CHANNEL_SIZE,
+    expand_logfiles, parse_timestamp, AsyncBufReaderSummarizer, BoxedParser, BufReaderSummarizer,
+    Checkpoint, CompositeParser, DistinctParser, DocumentParser, ErrorSummary, Filter,
+    FilterParser, GroupLayout, JsonParser, JsonPath, LogParserError, MainParser,
+    Parser as LineParser, PrintOptions, RayonMmapSummarizer, RejectFile, Sampling, Summarizer,
+    Summary, SummaryFile, SummaryOptions, TimeBucket, TimeWindow, TimestampParser, TopBy, TopK,
+    TypeFilter, TypeFilterParser, TypePattern, DEFAULT_BATCH_SIZE, DEFAULT_BUFFER_CAPACITY,
+    DEFAULT_CHUNK_SIZE, DEFAULT_DELIMITER, DEFAULT_FOLLOW_INTERVAL_MS, DEFAULT_GROUP_KEY,
+    DEFAULT_MAX_LINE_LENGTH, DEFAULT_REDUCER_CHANNEL_SIZE, DEFAULT_TIMESTAMP_FIELDS,
 };
-
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Simple CLI tool to extract JSON from a log file. By default, a human
 /// readable summary is printed to stdout. Both a synchronous and
@@ -20,45 +29,550 @@ struct Cli {
     /// The delimiter to use
     #[clap(long, short, default_value_t = DEFAULT_DELIMITER)]
     delimiter: u8,
+    /// The JSON field to group lines by. Nested fields can be selected
+    /// with a path e.g. `meta.kind` or `$.items[0].name`. Repeat to group
+    /// by several fields at once.
+    #[clap(long, short, default_value = DEFAULT_GROUP_KEY, multiple_occurrences = true)]
+    key: Vec<JsonPath>,
+    /// How to lay out multiple group keys: flat or nested
+    #[clap(long, default_value = "flat")]
+    layout: GroupLayout,
+    /// Print a summary for each file as well as the total
+    #[clap(long)]
+    per_file: bool,
+    /// Also save the summary to this file, so it can be merged with others
+    /// later using `merge`
+    #[clap(long)]
+    save: Option<String>,
+    /// Also break down each group over time: minute, hour or day
+    #[clap(long)]
+    bucket: Option<TimeBucket>,
+    /// The fields holding each line's timestamp, in RFC 3339 or epoch
+    /// milliseconds. The first one found is used.
+    #[clap(long, default_values = &DEFAULT_TIMESTAMP_FIELDS, multiple_occurrences = true)]
+    timestamp_field: Vec<String>,
+    /// Only summarize lines from this time on, in RFC 3339 or epoch
+    /// milliseconds
+    #[clap(long, parse(try_from_str = parse_time))]
+    since: Option<i64>,
+    /// Only summarize lines before this time, in RFC 3339 or epoch
+    /// milliseconds
+    #[clap(long, parse(try_from_str = parse_time))]
+    until: Option<i64>,
+    /// The input is in time order, so stop reading once a line is past
+    /// `--until`
+    #[clap(long)]
+    ordered: bool,
+    /// Only summarize lines matching an expression, e.g.
+    /// `level == "error" && service != "health"`
+    #[clap(long)]
+    filter: Option<Filter>,
+    /// Only summarize these types. Accepts exact values, globs like
+    /// `order.*` and regexes like `/^user_\d+$/`. Repeat to allow several.
+    #[clap(long, multiple_occurrences = true)]
+    include_type: Vec<TypePattern>,
+    /// Don't summarize these types, e.g. `heartbeat`. Takes the same
+    /// patterns as `--include-type`.
+    #[clap(long, multiple_occurrences = true)]
+    exclude_type: Vec<TypePattern>,
+    /// Only keep the K heaviest groups, using bounded memory. Their sizes
+    /// are estimates, shown with how far off they could be.
+    #[clap(long, value_name = "K", conflicts_with = "bucket")]
+    top: Option<usize>,
+    /// What to rank groups by with `--top`: bytes or lines
+    #[clap(long, default_value = "bytes")]
+    top_by: TopBy,
+    /// Estimate how many distinct values of this field each group has,
+    /// e.g. `user_id`. Only string values are counted.
+    #[clap(long, conflicts_with = "top")]
+    distinct: Option<JsonPath>,
+    /// Infer each group's schema: which fields appear, how often, and with
+    /// which types of value. With `--json` it's written as JSON Schema.
+    #[clap(long, conflicts_with = "top")]
+    schema: bool,
+    /// Keep N example lines from each group, chosen uniformly at random
+    #[clap(long, value_name = "N", conflicts_with = "top")]
+    samples: Option<usize>,
+    /// Seed the choice of `--samples`, so the same input gives the same
+    /// examples. Random by default.
+    #[clap(long, requires = "samples")]
+    seed: Option<u64>,
+    /// Write every line which fails to parse to this file, as it was read
+    #[clap(long, value_name = "PATH")]
+    reject_file: Option<String>,
+    /// Put the file, line number and reason before each line in the
+    /// reject file, separated from the line by a tab
+    #[clap(long, requires = "reject-file")]
+    annotate_rejects: bool,
+}
+
+fn parse_time(s: &str) -> Result<i64, String> {
+    parse_timestamp(s)
+        .ok_or_else(|| format!("`{}` is neither an RFC 3339 date nor epoch milliseconds", s))
 }
 
 #[derive(Debug, Subcommand)]
 enum Commands {
     Sync {
-        file: String,
+        /// The log files to read, or `-` for stdin. Globs are expanded and
+        /// directories are read recursively.
+        #[clap(required = true)]
+        files: Vec<String>,
         #[clap(short, long, help = "Length of the line buffer", default_value_t = DEFAULT_BUFFER_CAPACITY)]
         buffer_capacity: usize,
+        /// A state file to resume from, and save progress to afterwards.
+        /// Files which have only been appended to since are read from where
+        /// the last run stopped.
+        #[clap(long)]
+        checkpoint: Option<String>,
     },
     Async {
-        file: String,
+        /// The log files to read, or `-` for stdin. Globs are expanded and
+        /// directories are read recursively.
+        #[clap(required = true)]
+        files: Vec<String>,
         #[clap(short, long, help = "The size in bytes of each read batch", default_value_t = DEFAULT_BATCH_SIZE)]
         batch_size: usize,
         #[clap(short, long, help = "Maximum messages stored in the reducer queue.", default_value_t = DEFAULT_REDUCER_CHANNEL_SIZE)]
         reducer_channel_size: usize,
+        #[clap(short, long, help = "Lines longer than this many bytes are skipped and counted as errors", default_value_t = DEFAULT_MAX_LINE_LENGTH)]
+        max_line_length: usize,
     },
+    Rayon {
+        /// The log files to map in to memory. Globs are expanded and
+        /// directories are read recursively.
+        #[clap(required = true)]
+        files: Vec<String>,
+        #[clap(short, long, help = "The size in bytes of each chunk handed to a worker", default_value_t = DEFAULT_CHUNK_SIZE)]
+        chunk_size: usize,
+    },
+    /// Combine summaries saved with `--save`, e.g. from several hosts, in
+    /// to one report
+    Merge {
+        /// The summary files to merge
+        #[clap(required = true)]
+        files: Vec<String>,
+    },
+    /// Compare two log files, or with `--saved` two saved summaries, and
+    /// show how much each group grew or shrank
+    Diff {
+        /// The earlier log file or summary
+        before: String,
+        /// The later log file or summary
+        after: String,
+        /// Read summaries saved with `--save` rather than log files
+        #[clap(long)]
+        saved: bool,
+    },
+    /// Keep reading a log file as it grows, like `tail -f`, refreshing the
+    /// summary as it goes. Prints a JSON summary per line with `--json`.
+    Follow {
+        /// The log file to follow
+        file: String,
+        #[clap(short, long, help = "Milliseconds to wait between reads", default_value_t = DEFAULT_FOLLOW_INTERVAL_MS)]
+        interval: u64,
+        #[clap(short, long, help = "The size in bytes of each read batch", default_value_t = DEFAULT_BATCH_SIZE)]
+        batch_size: usize,
+        #[clap(short, long, help = "Lines longer than this many bytes are skipped and counted as errors", default_value_t = DEFAULT_MAX_LINE_LENGTH)]
+        max_line_length: usize,
+    },
+}
+
+/// Something to do once we know which parser to use. The parser's boxed,
+/// so each task is only compiled once, rather than for every combination
+/// of options.
+trait WithParser {
+    fn run(self, parser: BoxedParser) -> Result<(), LogParserError>;
+}
+
+/// Top level keys can use the fast string search, anything nested needs
+/// a real JSON parser
+fn with_parser<W: WithParser>(
+    keys: Vec<JsonPath>,
+    lines: &LineOptions,
+    task: W,
+) -> Result<(), LogParserError> {
+    if keys.iter().all(|key| key.as_key().is_some()) {
+        let parsers = keys
+            .iter()
+            .filter_map(|key| key.as_key().map(MainParser::new))
+            .collect();
+        with_timestamps(task, CompositeParser::from_parsers(parsers), lines)
+    } else {
+        let parsers = keys.into_iter().map(JsonParser::with_path).collect();
+        with_timestamps(task, CompositeParser::from_parsers(parsers), lines)
+    }
+}
+
+/// Like the group key, a top level field can use the fast string search.
+/// This and the documents wrap the filters, so filtered lines are never
+/// searched or parsed in full.
+fn with_distinct<W: WithParser, P: LineParser>(
+    task: W,
+    parser: P,
+    lines: &LineOptions,
+) -> Result<(), LogParserError> {
+    match &lines.distinct {
+        Some(field) => match field.as_key() {
+            Some(key) => with_documents(
+                task,
+                DistinctParser::with_field(parser, MainParser::new(key)),
+                lines,
+            ),
+            None => with_documents(
+                task,
+                DistinctParser::with_field(parser, JsonParser::with_path(field.clone())),
+                lines,
+            ),
+        },
+        None => with_documents(task, parser, lines),
+    }
+}
+
+/// Parsing every line in full is slow, so only do it for a schema
+fn with_documents<W: WithParser, P: LineParser>(
+    task: W,
+    parser: P,
+    lines: &LineOptions,
+) -> Result<(), LogParserError> {
+    if lines.schema {
+        task.run(BoxedParser::with_parser(DocumentParser::with_parser(
+            parser,
+        )))
+    } else {
+        task.run(BoxedParser::with_parser(parser))
+    }
+}
+
+/// Which lines to summarize, and what else to read from them
+struct LineOptions<'a> {
+    timestamps: Option<Timestamps<'a>>,
+    filter: Option<Filter>,
+    types: TypeFilter,
+    /// The field to count distinct values of
+    distinct: Option<JsonPath>,
+    schema: bool,
+}
+
+/// Where to find each line's timestamp, and which lines to keep
+struct Timestamps<'a> {
+    fields: &'a [String],
+    window: Option<TimeWindow>,
+}
+
+/// Only look for timestamps when they're needed
+fn with_timestamps<W: WithParser, P: LineParser>(
+    task: W,
+    parser: P,
+    lines: &LineOptions,
+) -> Result<(), LogParserError> {
+    let timestamps = match &lines.timestamps {
+        Some(timestamps) => timestamps,
+        None => return with_filter(task, parser, lines),
+    };
+    let parser = TimestampParser::with_fields(parser, timestamps.fields);
+    match timestamps.window {
+        Some(window) => with_filter(task, parser.within(window), lines),
+        None => with_filter(task, parser, lines),
+    }
+}
+
+fn with_filter<W: WithParser, P: LineParser>(
+    task: W,
+    parser: P,
+    lines: &LineOptions,
+) -> Result<(), LogParserError> {
+    match &lines.filter {
+        Some(filter) => with_types(
+            task,
+            FilterParser::with_filter(parser, filter.clone()),
+            lines,
+        ),
+        None => with_types(task, parser, lines),
+    }
+}
+
+fn with_types<W: WithParser, P: LineParser>(
+    task: W,
+    parser: P,
+    lines: &LineOptions,
+) -> Result<(), LogParserError> {
+    if lines.types.is_empty() {
+        with_distinct(task, parser, lines)
+    } else {
+        with_distinct(
+            task,
+            TypeFilterParser::with_filter(parser, lines.types.clone()),
+            lines,
+        )
+    }
+}
+
+struct SummarizeFiles<'a, S> {
+    summarizer: S,
+    files: &'a [String],
+    options: &'a PrintOptions,
+    save: Option<&'a str>,
+}
+
+/// Combine the summaries of each file and save them to `path`
+fn save_summaries<E: ErrorSummary>(
+    path: Option<&str>,
+    files: &[String],
+    summaries: &[Summary<E>],
+) -> Result<(), LogParserError> {
+    if let Some(path) = path {
+        let mut saved = Summary::new();
+        for (file, summary) in files.iter().zip(summaries) {
+            saved.combine(summary.to_saved(file));
+        }
+        SummaryFile::new(saved).save(path)?;
+    }
+    Ok(())
+}
+
+impl<S: Summarizer> WithParser for SummarizeFiles<'_, S>
+where
+    LogParserError: From<S::SummarizerError>,
+{
+    fn run(self, parser: BoxedParser) -> Result<(), LogParserError> {
+        let files = expand_logfiles(self.files)?;
+        let summaries = self.summarizer.summarize_files(&files, parser)?;
+        save_summaries(self.save, &files, &summaries)?;
+        Summary::print_files(files.into_iter().zip(summaries).collect(), self.options);
+        Ok(())
+    }
+}
+
+struct Resume<'a> {
+    summarizer: BufReaderSummarizer,
+    files: &'a [String],
+    checkpoint: &'a str,
+    options: &'a PrintOptions,
+    save: Option<&'a str>,
+}
+
+impl WithParser for Resume<'_> {
+    fn run(self, parser: BoxedParser) -> Result<(), LogParserError> {
+        let files = expand_logfiles(self.files)?;
+        let checkpoint = Checkpoint::load(self.checkpoint)?;
+        let (summaries, checkpoint) =
+            self.summarizer
+                .summarize_files_resumable(&files, parser, &checkpoint)?;
+        checkpoint.save(self.checkpoint)?;
+        save_summaries(self.save, &files, &summaries)?;
+        Summary::print_files(files.into_iter().zip(summaries).collect(), self.options);
+        Ok(())
+    }
+}
+
+struct Diff<'a> {
+    summarizer: BufReaderSummarizer,
+    before: &'a str,
+    after: &'a str,
+    options: &'a PrintOptions,
+}
+
+impl WithParser for Diff<'_> {
+    fn run(self, parser: BoxedParser) -> Result<(), LogParserError> {
+        let files = [self.before.to_owned(), self.after.to_owned()];
+        let summaries = self.summarizer.summarize_files(&files, parser)?;
+        summaries[0]
+            .diff(&summaries[1], self.options)?
+            .print(self.options);
+        Ok(())
+    }
+}
+
+struct Follow<'a> {
+    summarizer: AsyncBufReaderSummarizer,
+    file: &'a str,
+    interval: Duration,
+    options: &'a PrintOptions,
+}
+
+impl WithParser for Follow<'_> {
+    fn run(self, parser: BoxedParser) -> Result<(), LogParserError> {
+        let options = self.options;
+        self.summarizer
+            .follow(self.file, parser, self.interval, |summary| {
+                summary.print_snapshot(options);
+                true
+            })?;
+        Ok(())
+    }
 }
 
 fn main() -> Result<(), LogParserError> {
     let cmd = Cli::parse();
     let delim = cmd.delimiter;
+    let field_name = |key: &JsonPath| key.as_key().map_or_else(|| key.to_string(), str::to_owned);
+    let options = PrintOptions {
+        json: cmd.json,
+        layout: cmd.layout,
+        group_by: cmd.key.iter().map(field_name).collect(),
+        per_file: cmd.per_file,
+        bucket: cmd.bucket,
+        distinct: cmd.distinct.as_ref().map(field_name),
+    };
+    let window = if cmd.since.is_some() || cmd.until.is_some() {
+        Some(TimeWindow::new(cmd.since, cmd.until, cmd.ordered))
+    } else {
+        None
+    };
+    let lines = LineOptions {
+        timestamps: if cmd.bucket.is_some() || window.is_some() {
+            Some(Timestamps {
+                fields: &cmd.timestamp_field,
+                window,
+            })
+        } else {
+            None
+        },
+        filter: cmd.filter,
+        types: TypeFilter::new(cmd.include_type, cmd.exclude_type),
+        distinct: cmd.distinct,
+        schema: cmd.schema,
+    };
+    let top_k = cmd.top.map(|k| TopK::new(k, cmd.top_by));
+    let bucketing = cmd.bucket.is_some();
+    let seed = cmd
+        .seed
+        .unwrap_or_else(|| RandomState::new().build_hasher().finish());
+    let sampling = cmd.samples.map(|samples| Sampling::new(samples, seed));
+    let rejects = match &cmd.reject_file {
+        Some(path) => Some(Arc::new(RejectFile::create(path, cmd.annotate_rejects)?)),
+        None => None,
+    };
+    let summary_options = SummaryOptions::new()
+        .with_top_k(top_k)
+        .with_buckets(bucketing)
+        .with_samples(sampling)
+        .with_rejects(rejects);
     match cmd.command {
         Commands::Sync {
-            file,
+            files,
             buffer_capacity,
-        } => {
-            let summary = BufReaderSummarizer::new(buffer_capacity, delim)
-                .summarize::<MainParser>(file.as_str())?;
-            summary.print(cmd.json);
-        }
+            checkpoint: Some(checkpoint),
+        } => with_parser(
+            cmd.key,
+            &lines,
+            Resume {
+                summarizer: BufReaderSummarizer::new(buffer_capacity, delim)
+                    .with_options(summary_options.clone()),
+                files: &files,
+                checkpoint: &checkpoint,
+                options: &options,
+                save: cmd.save.as_deref(),
+            },
+        ),
+        Commands::Sync {
+            files,
+            buffer_capacity,
+            checkpoint: None,
+        } => with_parser(
+            cmd.key,
+            &lines,
+            SummarizeFiles {
+                summarizer: BufReaderSummarizer::new(buffer_capacity, delim)
+                    .with_options(summary_options.clone()),
+                files: &files,
+                options: &options,
+                save: cmd.save.as_deref(),
+            },
+        ),
         Commands::Async {
-            file,
+            files,
             batch_size,
             reducer_channel_size,
+            max_line_length,
+        } => with_parser(
+            cmd.key,
+            &lines,
+            SummarizeFiles {
+                summarizer: AsyncBufReaderSummarizer::new(
+                    reducer_channel_size,
+                    batch_size,
+                    delim,
+                    max_line_length,
+                )
+                .with_options(summary_options.clone()),
+                files: &files,
+                options: &options,
+                save: cmd.save.as_deref(),
+            },
+        ),
+        Commands::Rayon { files, chunk_size } => with_parser(
+            cmd.key,
+            &lines,
+            SummarizeFiles {
+                summarizer: RayonMmapSummarizer::new(chunk_size, delim)
+                    .with_options(summary_options.clone()),
+                files: &files,
+                options: &options,
+                save: cmd.save.as_deref(),
+            },
+        ),
+        Commands::Merge { files } => {
+            let files = expand_logfiles(&files)?;
+            let summaries = files
+                .iter()
+                .map(SummaryFile::load)
+                .collect::<Result<Vec<_>, _>>()?;
+            if let Some(path) = cmd.save.as_deref() {
+                let mut merged = Summary::new();
+                for summary in summaries.iter() {
+                    merged.combine(summary.clone());
+                }
+                SummaryFile::new(merged).save(path)?;
+            }
+            Summary::print_files(files.into_iter().zip(summaries).collect(), &options);
+            Ok(())
+        }
+        Commands::Diff {
+            before,
+            after,
+            saved: true,
         } => {
-            let summary = AsyncBufReaderSummarizer::new(reducer_channel_size, batch_size, delim)
-                .summarize::<MainParser>(file.as_str())?;
-            summary.print(cmd.json);
+            let before = SummaryFile::load(&before)?;
+            let after = SummaryFile::load(&after)?;
+            before.diff(&after, &options)?.print(&options);
+            Ok(())
         }
+        Commands::Diff {
+            before,
+            after,
+            saved: false,
+        } => with_parser(
+            cmd.key,
+            &lines,
+            Diff {
+                summarizer: BufReaderSummarizer::new(DEFAULT_BUFFER_CAPACITY, delim),
+                before: &before,
+                after: &after,
+                options: &options,
+            },
+        ),
+        Commands::Follow {
+            file,
+            interval,
+            batch_size,
+            max_line_length,
+        } => with_parser(
+            cmd.key,
+            &lines,
+            Follow {
+                summarizer: AsyncBufReaderSummarizer::new(
+                    DEFAULT_REDUCER_CHANNEL_SIZE,
+                    batch_size,
+                    delim,
+                    max_line_length,
+                )
+                .with_options(summary_options.clone()),
+                file: &file,
+                interval: Duration::from_millis(interval),
+                options: &options,
+            },
+        ),
     }
-    Ok(())
 }
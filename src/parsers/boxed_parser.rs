@@ -0,0 +1,81 @@
+use crate::{LogLineMetadata, MainParser, Parser};
+use std::fmt::{Debug, Display, Formatter};
+
+/// Any parser behind a pointer, so code which is generic over parsers,
+/// like the summarizers, is only compiled once however many combinations
+/// of wrapped parsers are chosen at runtime. Costs a virtual call per line.
+pub struct BoxedParser {
+    inner: Box<dyn DynParser>,
+}
+
+impl BoxedParser {
+    pub fn with_parser<P: Parser>(inner: P) -> Self {
+        Self {
+            inner: Box::new(inner),
+        }
+    }
+}
+
+impl Parser for BoxedParser {
+    type Error = BoxedParserError;
+
+    /// Boxes the [MainParser]
+    fn new(group_key: &str) -> Self {
+        Self::with_parser(MainParser::new(group_key))
+    }
+
+    fn parse<'a>(&self, line: &'a [u8]) -> Result<LogLineMetadata<'a>, Self::Error> {
+        self.inner.parse_boxed(line)
+    }
+}
+
+/// Any parser's error. They're usually empty, so boxing them is free.
+pub struct BoxedParserError(Box<dyn ParserError>);
+
+impl Debug for BoxedParserError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.0, f)
+    }
+}
+
+impl Display for BoxedParserError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+/// What [Parser] requires of an error, as one trait to point at
+trait ParserError: Debug + Display {}
+
+impl<E: Debug + Display> ParserError for E {}
+
+/// The object safe part of [Parser]
+trait DynParser: Send + Sync {
+    fn parse_boxed<'a>(&self, line: &'a [u8]) -> Result<LogLineMetadata<'a>, BoxedParserError>;
+}
+
+impl<P: Parser> DynParser for P {
+    fn parse_boxed<'a>(&self, line: &'a [u8]) -> Result<LogLineMetadata<'a>, BoxedParserError> {
+        self.parse(line)
+            .map_err(|err| BoxedParserError(Box::new(err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{StringParser, TypeFilter, TypeFilterParser};
+
+    #[test]
+    fn test_boxed_parser() {
+        let filter = TypeFilter::new(vec![], vec!["b".parse().unwrap()]);
+        let parser = BoxedParser::with_parser(TypeFilterParser::with_filter(
+            StringParser::new("type"),
+            filter,
+        ));
+        let metadata = parser.parse(br#"{"type":"a"}"#).unwrap();
+        assert_eq!(metadata.type_name.as_ref(), b"a");
+        let err = parser.parse(b"nope").unwrap_err();
+        assert_eq!(err.to_string(), "no string value for the group key");
+    }
+}
@@ -0,0 +1,106 @@
+use crate::parsers::KEY_SEPARATOR;
+use crate::{LogLineMetadata, Parser};
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+
+/// Groups by several fields at once by running one parser per field and
+/// joining the values with [KEY_SEPARATOR]. A single field is passed
+/// straight through, so it stays zero-copy. Values containing the separator
+/// are rejected, as the key couldn't be split back into its fields.
+pub struct CompositeParser<P: Parser> {
+    parsers: Vec<P>,
+}
+
+impl<P: Parser> CompositeParser<P> {
+    /// One parser per field, in the order the key should be built
+    pub fn from_parsers(parsers: Vec<P>) -> Self {
+        assert!(!parsers.is_empty(), "at least one group key is required");
+        Self { parsers }
+    }
+}
+
+/// Why a line's group key couldn't be built
+#[derive(Debug)]
+pub enum CompositeParserError<E> {
+    /// One of the fields failed to parse
+    Field(E),
+    /// One of the values contains [KEY_SEPARATOR]
+    Separator,
+}
+
+impl<E: Display> Display for CompositeParserError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Field(err) => err.fmt(f),
+            Self::Separator => f.write_str("group key value contains the key separator"),
+        }
+    }
+}
+
+impl<P: Parser> Parser for CompositeParser<P> {
+    type Error = CompositeParserError<P::Error>;
+
+    fn new(group_key: &str) -> Self {
+        Self::from_parsers(vec![P::new(group_key)])
+    }
+
+    fn parse<'a>(&self, line: &'a [u8]) -> Result<LogLineMetadata<'a>, Self::Error> {
+        let parse = |parser: &P| match parser.parse(line) {
+            Ok(metadata) if metadata.type_name.contains(&KEY_SEPARATOR) => {
+                Err(CompositeParserError::Separator)
+            }
+            result => result.map_err(CompositeParserError::Field),
+        };
+        if let [parser] = self.parsers.as_slice() {
+            return parse(parser);
+        }
+        let mut type_name = Vec::new();
+        for (i, parser) in self.parsers.iter().enumerate() {
+            if i > 0 {
+                type_name.push(KEY_SEPARATOR);
+            }
+            type_name.extend_from_slice(&parse(parser)?.type_name);
+        }
+        Ok(LogLineMetadata::new(Cow::Owned(type_name), line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JsonParser, StringParser};
+
+    #[test]
+    fn test_composite_parse() {
+        let parser = CompositeParser::from_parsers(vec![
+            StringParser::new("type"),
+            StringParser::new("service"),
+        ]);
+        let line = b"{\"service\":\"billing\",\"type\":\"audit\"}";
+        let result = parser.parse(line).unwrap();
+        assert_eq!(&*result.type_name, b"audit\x1fbilling");
+        assert_eq!(result.bytes, line.len());
+        assert!(parser.parse(b"{\"type\":\"audit\"}").is_err());
+    }
+
+    #[test]
+    fn test_composite_parse_single_key_borrows() {
+        let parser = CompositeParser::<StringParser>::new("type");
+        let result = parser.parse(b"{\"type\":\"audit\"}").unwrap();
+        assert!(matches!(result.type_name, Cow::Borrowed(b"audit")));
+    }
+
+    #[test]
+    fn test_composite_parse_rejects_separator() {
+        let line = br#"{"service":"billing","type":"audit\u001fbilling"}"#;
+        let single = CompositeParser::<JsonParser>::new("type");
+        let composite = CompositeParser::from_parsers(vec![
+            JsonParser::new("type"),
+            JsonParser::new("service"),
+        ]);
+        for parser in [single, composite] {
+            let err = parser.parse(line).unwrap_err();
+            assert!(matches!(err, CompositeParserError::Separator));
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{LineFilter, LogLineMetadata, Parser};
+
+/// Wraps another parser to also pull out the value of a second field, using
+/// `field` as if it were grouping by it. Lines without the field still
+/// parse, they just have no value. Lines the inner parser has already
+/// filtered out aren't searched.
+pub struct DistinctParser<P: Parser, D: Parser> {
+    inner: P,
+    field: Option<D>,
+}
+
+impl<P: Parser, D: Parser> DistinctParser<P, D> {
+    pub fn with_field(inner: P, field: D) -> Self {
+        Self {
+            inner,
+            field: Some(field),
+        }
+    }
+}
+
+impl<P: Parser, D: Parser> Parser for DistinctParser<P, D> {
+    type Error = P::Error;
+
+    /// Without a field nothing is counted
+    fn new(group_key: &str) -> Self {
+        Self {
+            inner: P::new(group_key),
+            field: None,
+        }
+    }
+
+    fn parse<'a>(&self, line: &'a [u8]) -> Result<LogLineMetadata<'a>, Self::Error> {
+        let mut metadata = self.inner.parse(line)?;
+        if let Some(field) = self
+            .field
+            .as_ref()
+            .filter(|_| metadata.filter == LineFilter::Keep)
+        {
+            metadata.distinct = field.parse(line).ok().map(|value| value.type_name);
+        }
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JsonParser, StringParser};
+
+    #[test]
+    fn test_distinct_parser() {
+        let parser = DistinctParser::with_field(
+            StringParser::new("type"),
+            JsonParser::with_path("user.id".parse().unwrap()),
+        );
+        let metadata = parser.parse(br#"{"type":"a","user":{"id":"u1"}}"#).unwrap();
+        assert_eq!(&*metadata.type_name, b"a");
+        assert_eq!(metadata.distinct.as_deref(), Some(&b"u1"[..]));
+        assert_eq!(parser.parse(br#"{"type":"a"}"#).unwrap().distinct, None);
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{LineFilter, LogLineMetadata, Parser};
+
+/// Wraps another parser to also parse each line as a whole JSON document,
+/// e.g. to infer its schema. This is much slower than only finding the
+/// group key, so it's only worth it when the document is needed. Lines
+/// which aren't JSON still parse if the inner parser allows, they just
+/// have no document, as do lines the inner parser has filtered out.
+pub struct DocumentParser<P: Parser> {
+    inner: P,
+}
+
+impl<P: Parser> DocumentParser<P> {
+    pub fn with_parser(inner: P) -> Self {
+        Self { inner }
+    }
+}
+
+impl<P: Parser> Parser for DocumentParser<P> {
+    type Error = P::Error;
+
+    fn new(group_key: &str) -> Self {
+        Self::with_parser(P::new(group_key))
+    }
+
+    fn parse<'a>(&self, line: &'a [u8]) -> Result<LogLineMetadata<'a>, Self::Error> {
+        let mut metadata = self.inner.parse(line)?;
+        if metadata.filter == LineFilter::Keep {
+            metadata.document = serde_json::from_slice(line).ok();
+        }
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{StringParser, TypeFilter, TypeFilterParser};
+    use serde_json::json;
+
+    #[test]
+    fn test_document_parser() {
+        let parser = DocumentParser::<StringParser>::new("type");
+        let metadata = parser.parse(br#"{"type":"a","n":[1,null]}"#).unwrap();
+        assert_eq!(
+            metadata.document,
+            Some(json!({"type": "a", "n": [1, null]}))
+        );
+        let metadata = parser.parse(br#"{"type":"a", oops}"#).unwrap();
+        assert_eq!(metadata.document, None);
+        // excluded types aren't worth parsing in full
+        let filter = TypeFilter::new(vec![], vec!["a".parse().unwrap()]);
+        let parser = DocumentParser::with_parser(TypeFilterParser::with_filter(
+            StringParser::new("type"),
+            filter,
+        ));
+        let metadata = parser.parse(br#"{"type":"a","n":1}"#).unwrap();
+        assert_eq!(metadata.filter, LineFilter::Exclude);
+        assert_eq!(metadata.document, None);
+    }
+}
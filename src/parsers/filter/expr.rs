@@ -0,0 +1,397 @@
+use crate::parsers::filter::fields::FieldValue;
+use crate::parsers::filter::FilterError;
+use crate::JsonPath;
+use regex::Regex;
+use std::cmp::Ordering;
+
+/// A constant in an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A parsed filter. Fields are referred to by their index in the list of
+/// paths the expression uses, so each is only looked up once per line.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Exists(usize),
+    Compare(usize, CompareOp, Literal),
+    In(usize, Vec<Literal>),
+    Matches(usize, Regex),
+}
+
+/// Only values of the same JSON type can be ordered
+fn compare(value: &FieldValue<'_>, literal: &Literal) -> Option<Ordering> {
+    match (value, literal) {
+        (FieldValue::Null, Literal::Null) => Some(Ordering::Equal),
+        (FieldValue::Bool(a), Literal::Bool(b)) => Some(a.cmp(b)),
+        (FieldValue::Number(a), Literal::Number(b)) => a.partial_cmp(b),
+        (FieldValue::String(a), Literal::String(b)) => Some(a.as_ref().cmp(b.as_str())),
+        _ => None,
+    }
+}
+
+impl Expr {
+    /// `fields` holds the value of each path, if it was in the line.
+    /// Missing fields are unequal to everything, and can't be ordered.
+    pub fn eval(&self, fields: &[Option<FieldValue<'_>>]) -> bool {
+        let ordering =
+            |field: usize, literal| fields[field].as_ref().and_then(|v| compare(v, literal));
+        match self {
+            Expr::And(a, b) => a.eval(fields) && b.eval(fields),
+            Expr::Or(a, b) => a.eval(fields) || b.eval(fields),
+            Expr::Not(a) => !a.eval(fields),
+            Expr::Exists(field) => fields[*field].is_some(),
+            Expr::Compare(field, op, literal) => {
+                let ordering = ordering(*field, literal);
+                match op {
+                    CompareOp::Eq => ordering == Some(Ordering::Equal),
+                    CompareOp::Ne => ordering != Some(Ordering::Equal),
+                    CompareOp::Lt => ordering == Some(Ordering::Less),
+                    CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                    CompareOp::Gt => ordering == Some(Ordering::Greater),
+                    CompareOp::Ge => {
+                        matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+                    }
+                }
+            }
+            Expr::In(field, literals) => literals
+                .iter()
+                .any(|literal| ordering(*field, literal) == Some(Ordering::Equal)),
+            Expr::Matches(field, regex) => match &fields[*field] {
+                Some(FieldValue::String(s)) => regex.is_match(s),
+                _ => false,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    Comma,
+    And,
+    Or,
+    Not,
+    Compare(CompareOp),
+    Match,
+    In,
+    Exists,
+    Literal(Literal),
+    Path(String),
+}
+
+fn error<T>(position: usize, reason: &str) -> Result<T, FilterError> {
+    Err(FilterError {
+        position,
+        reason: reason.to_owned(),
+    })
+}
+
+/// Can appear in an unquoted field name
+fn is_word(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'_' | b'$' | b'@' | b'-')
+}
+
+/// Split an expression in to tokens, each with its byte offset
+fn tokenize(s: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    let bytes = s.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let two = bytes.get(i..i + 2);
+        let token = match bytes[i] {
+            c if c.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'(' => Token::LParen,
+            b')' => Token::RParen,
+            b'[' => Token::LBracket,
+            b']' => Token::RBracket,
+            b',' => Token::Comma,
+            _ if two == Some(b"&&") => Token::And,
+            _ if two == Some(b"||") => Token::Or,
+            _ if two == Some(b"==") => Token::Compare(CompareOp::Eq),
+            _ if two == Some(b"!=") => Token::Compare(CompareOp::Ne),
+            _ if two == Some(b"<=") => Token::Compare(CompareOp::Le),
+            _ if two == Some(b">=") => Token::Compare(CompareOp::Ge),
+            _ if two == Some(b"=~") => Token::Match,
+            b'<' => Token::Compare(CompareOp::Lt),
+            b'>' => Token::Compare(CompareOp::Gt),
+            b'!' => Token::Not,
+            b'"' => {
+                let (literal, end) = string_literal(s, i)?;
+                i = end;
+                tokens.push((start, Token::Literal(Literal::String(literal))));
+                continue;
+            }
+            c if c.is_ascii_digit() || c == b'-' => {
+                let len = bytes[i..]
+                    .iter()
+                    .position(|&c| !(c.is_ascii_alphanumeric() || matches!(c, b'.' | b'-' | b'+')))
+                    .unwrap_or(bytes.len() - i);
+                let number = match s[i..i + len].parse() {
+                    Ok(number) => number,
+                    Err(_) => return error(i, "invalid number"),
+                };
+                i += len;
+                tokens.push((start, Token::Literal(Literal::Number(number))));
+                continue;
+            }
+            c if is_word(c) => {
+                let word_len = bytes[i..]
+                    .iter()
+                    .position(|&c| !is_word(c))
+                    .unwrap_or(bytes.len() - i);
+                let token = match &s[i..i + word_len] {
+                    "in" => Some(Token::In),
+                    "exists" => Some(Token::Exists),
+                    "true" => Some(Token::Literal(Literal::Bool(true))),
+                    "false" => Some(Token::Literal(Literal::Bool(false))),
+                    "null" => Some(Token::Literal(Literal::Null)),
+                    _ => None,
+                };
+                if let Some(token) = token {
+                    i += word_len;
+                    tokens.push((start, token));
+                } else {
+                    i = path_end(bytes, i)?;
+                    tokens.push((start, Token::Path(s[start..i].to_owned())));
+                }
+                continue;
+            }
+            _ => return error(i, "unexpected character"),
+        };
+        i += match token {
+            Token::And
+            | Token::Or
+            | Token::Match
+            | Token::Compare(CompareOp::Eq | CompareOp::Ne | CompareOp::Le | CompareOp::Ge) => 2,
+            _ => 1,
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+/// The end of a field path starting at `i`, e.g. `meta.kind`,
+/// `items[0]` or `$["service.name"]`
+fn path_end(bytes: &[u8], mut i: usize) -> Result<usize, FilterError> {
+    while i < bytes.len() {
+        match bytes[i] {
+            c if is_word(c) || c == b'.' => i += 1,
+            b'[' => match bytes[i..].iter().position(|&c| c == b']') {
+                Some(close) => i += close + 1,
+                None => return error(i, "unclosed `[`"),
+            },
+            _ => break,
+        }
+    }
+    Ok(i)
+}
+
+/// A double quoted string starting at `start`, with `\"` and `\\`
+/// escapes. Returns the string and the offset just past it.
+fn string_literal(s: &str, start: usize) -> Result<(String, usize), FilterError> {
+    let mut literal = String::new();
+    let mut chars = s[start + 1..].char_indices();
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            '"' => return Ok((literal, start + 1 + offset + 1)),
+            '\\' => match chars.next() {
+                Some((_, c @ ('"' | '\\'))) => literal.push(c),
+                _ => return error(start + 1 + offset, "unknown escape"),
+            },
+            c => literal.push(c),
+        }
+    }
+    error(start, "unclosed string")
+}
+
+/// Recursive descent over the tokens. From loosest to tightest binding:
+/// `||`, `&&`, `!` then comparisons and parentheses.
+pub struct ExprParser<'a> {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    /// The length of the source, for errors at the end
+    len: usize,
+    /// Every path used so far
+    pub fields: &'a mut Vec<JsonPath>,
+}
+
+impl<'a> ExprParser<'a> {
+    pub fn parse(s: &str, fields: &'a mut Vec<JsonPath>) -> Result<Expr, FilterError> {
+        let mut parser = ExprParser {
+            tokens: tokenize(s)?,
+            next: 0,
+            len: s.len(),
+            fields,
+        };
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.next) {
+            None => Ok(expr),
+            Some((position, _)) => error(*position, "expected `&&`, `||` or the end"),
+        }
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map_or(self.len, |(position, _)| *position)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn take(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).map(|(_, token)| token.clone());
+        self.next += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token, reason: &str) -> Result<(), FilterError> {
+        let position = self.position();
+        match self.take() {
+            Some(token) if token == expected => Ok(()),
+            _ => error(position, reason),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, FilterError> {
+        let mut expr = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.next += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<Expr, FilterError> {
+        if self.peek() == Some(&Token::Not) {
+            self.next += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, FilterError> {
+        let position = self.position();
+        match self.take() {
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                self.expect(Token::RParen, "expected `)`")?;
+                Ok(expr)
+            }
+            Some(Token::Exists) => {
+                self.expect(Token::LParen, "expected `(` after exists")?;
+                let field = self.field()?;
+                self.expect(Token::RParen, "expected `)`")?;
+                Ok(Expr::Exists(field))
+            }
+            Some(Token::Path(_)) => {
+                self.next -= 1;
+                let field = self.field()?;
+                self.comparison(field)
+            }
+            _ => error(position, "expected a field, `!`, `(` or exists"),
+        }
+    }
+
+    /// What follows a field in a comparison
+    fn comparison(&mut self, field: usize) -> Result<Expr, FilterError> {
+        let position = self.position();
+        match self.take() {
+            Some(Token::Compare(op)) => Ok(Expr::Compare(field, op, self.literal()?)),
+            Some(Token::In) => {
+                self.expect(Token::LBracket, "expected `[` after in")?;
+                let mut literals = vec![self.literal()?];
+                while self.peek() == Some(&Token::Comma) {
+                    self.next += 1;
+                    literals.push(self.literal()?);
+                }
+                self.expect(Token::RBracket, "expected `,` or `]`")?;
+                Ok(Expr::In(field, literals))
+            }
+            Some(Token::Match) => {
+                let position = self.position();
+                match self.literal()? {
+                    Literal::String(pattern) => match Regex::new(&pattern) {
+                        Ok(regex) => Ok(Expr::Matches(field, regex)),
+                        Err(err) => Err(FilterError {
+                            position,
+                            reason: err.to_string(),
+                        }),
+                    },
+                    _ => error(position, "expected a regex string after `=~`"),
+                }
+            }
+            _ => error(
+                position,
+                "expected a comparison, `in` or `=~` after the field",
+            ),
+        }
+    }
+
+    /// A path, returning its index in the fields
+    fn field(&mut self) -> Result<usize, FilterError> {
+        let position = self.position();
+        let path: JsonPath = match self.take() {
+            Some(Token::Path(path)) => match path.parse() {
+                Ok(path) => path,
+                Err(err) => {
+                    return Err(FilterError {
+                        position,
+                        reason: err.to_string(),
+                    })
+                }
+            },
+            _ => return error(position, "expected a field"),
+        };
+        match self.fields.iter().position(|field| *field == path) {
+            Some(index) => Ok(index),
+            None => {
+                self.fields.push(path);
+                Ok(self.fields.len() - 1)
+            }
+        }
+    }
+
+    fn literal(&mut self) -> Result<Literal, FilterError> {
+        let position = self.position();
+        match self.take() {
+            Some(Token::Literal(literal)) => Ok(literal),
+            _ => error(position, "expected a string, number, true, false or null"),
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use crate::parsers::json_parser::MaybeBorrowed;
+use crate::parsers::json_path::{JsonPath, PathSegment};
+use serde::de::{DeserializeSeed, Error, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserializer;
+use std::borrow::Cow;
+use std::fmt::Formatter;
+
+/// A value in a line, borrowing strings from it unless they contain
+/// escapes. Objects and arrays are only noted, not kept.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue<'a> {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(Cow<'a, str>),
+    Object,
+    Array,
+}
+
+/// Find the value at each of `paths` in a single pass over `line`, or
+/// `None` if it isn't JSON
+pub fn extract_fields<'a>(
+    line: &'a [u8],
+    paths: &[JsonPath],
+) -> Option<Vec<Option<FieldValue<'a>>>> {
+    let mut found = vec![None; paths.len()];
+    let targets = paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| (i, path.segments()))
+        .collect();
+    let mut deserializer = serde_json::Deserializer::from_slice(line);
+    Fields {
+        targets,
+        found: &mut found,
+    }
+    .deserialize(&mut deserializer)
+    .ok()?;
+    deserializer.end().ok()?;
+    Some(found)
+}
+
+/// Walks a document, descending only in to values which some path still
+/// leads through
+struct Fields<'p, 'r, 'de> {
+    /// The rest of each path from here, with its index in `found`
+    targets: Vec<(usize, &'p [PathSegment])>,
+    found: &'r mut [Option<FieldValue<'de>>],
+}
+
+impl<'p, 'r, 'de> Fields<'p, 'r, 'de> {
+    /// Record `value` for every path which ends here
+    fn found_here(&mut self, value: FieldValue<'de>) {
+        for (i, path) in self.targets.iter() {
+            if path.is_empty() {
+                self.found[*i] = Some(value.clone());
+            }
+        }
+    }
+
+    /// The paths which continue in to the child whose segment matches
+    fn children<F: Fn(&PathSegment) -> bool>(
+        &self,
+        is_child: F,
+    ) -> Vec<(usize, &'p [PathSegment])> {
+        self.targets
+            .iter()
+            .filter_map(|(i, path)| match path.split_first() {
+                Some((first, rest)) if is_child(first) => Some((*i, rest)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl<'p, 'r, 'de> DeserializeSeed<'de> for Fields<'p, 'r, 'de> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'p, 'r, 'de> Visitor<'de> for Fields<'p, 'r, 'de> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("any JSON value")
+    }
+
+    fn visit_bool<E: Error>(mut self, v: bool) -> Result<Self::Value, E> {
+        self.found_here(FieldValue::Bool(v));
+        Ok(())
+    }
+
+    fn visit_i64<E: Error>(mut self, v: i64) -> Result<Self::Value, E> {
+        self.found_here(FieldValue::Number(v as f64));
+        Ok(())
+    }
+
+    fn visit_u64<E: Error>(mut self, v: u64) -> Result<Self::Value, E> {
+        self.found_here(FieldValue::Number(v as f64));
+        Ok(())
+    }
+
+    fn visit_f64<E: Error>(mut self, v: f64) -> Result<Self::Value, E> {
+        self.found_here(FieldValue::Number(v));
+        Ok(())
+    }
+
+    fn visit_borrowed_str<E: Error>(mut self, v: &'de str) -> Result<Self::Value, E> {
+        self.found_here(FieldValue::String(Cow::Borrowed(v)));
+        Ok(())
+    }
+
+    fn visit_str<E: Error>(mut self, v: &str) -> Result<Self::Value, E> {
+        self.found_here(FieldValue::String(Cow::Owned(v.to_owned())));
+        Ok(())
+    }
+
+    fn visit_unit<E: Error>(mut self) -> Result<Self::Value, E> {
+        self.found_here(FieldValue::Null);
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<Self::Value, A::Error> {
+        self.found_here(FieldValue::Object);
+        while let Some(MaybeBorrowed(key)) = map.next_key()? {
+            let targets =
+                self.children(|segment| matches!(segment, PathSegment::Key(k) if *k == key));
+            if targets.is_empty() {
+                map.next_value::<IgnoredAny>()?;
+            } else {
+                map.next_value_seed(Fields {
+                    targets,
+                    found: &mut *self.found,
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<Self::Value, A::Error> {
+        self.found_here(FieldValue::Array);
+        for index in 0.. {
+            let targets =
+                self.children(|segment| matches!(segment, PathSegment::Index(i) if *i == index));
+            let more = if targets.is_empty() {
+                seq.next_element::<IgnoredAny>()?.is_some()
+            } else {
+                seq.next_element_seed(Fields {
+                    targets,
+                    found: &mut *self.found,
+                })?
+                .is_some()
+            };
+            if !more {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_fields() {
+        let paths: Vec<JsonPath> = ["level", "meta.code", "meta", "tags[1]", "missing"]
+            .iter()
+            .map(|p| p.parse().unwrap())
+            .collect();
+        let line = br#"{"level":"error","meta":{"code":503,"ok":false},"tags":["a","b!"]}"#;
+        let found = extract_fields(line, &paths).unwrap();
+        assert!(matches!(
+            found[0],
+            Some(FieldValue::String(Cow::Borrowed("error")))
+        ));
+        assert_eq!(found[1], Some(FieldValue::Number(503.0)));
+        assert_eq!(found[2], Some(FieldValue::Object));
+        assert_eq!(
+            found[3],
+            Some(FieldValue::String(Cow::Owned("b!".to_owned())))
+        );
+        assert_eq!(found[4], None);
+        assert!(extract_fields(b"{\"level\":", &paths).is_none());
+    }
+}
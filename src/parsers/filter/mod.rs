@@ -0,0 +1,154 @@
+use crate::parsers::filter::expr::{Expr, ExprParser};
+use crate::parsers::filter::fields::extract_fields;
+use crate::{JsonPath, LineFilter, LogLineMetadata, Parser};
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+mod expr;
+mod fields;
+
+/// A predicate over the fields of a JSON line, e.g.
+/// `level == "error" && service != "health"`. Supports `==`, `!=`, `<`,
+/// `<=`, `>`, `>=`, `in ["a", "b"]`, regex matches with `=~ "^5\\d\\d$"`,
+/// `exists(field)`, `&&`, `||`, `!` and parentheses. Fields are
+/// [JsonPath]s, and are only compared with values of the same JSON type.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expr: Expr,
+    /// Every field the expression uses
+    fields: Vec<JsonPath>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FilterError {
+    /// The byte offset in the expression where parsing failed
+    position: usize,
+    reason: String,
+}
+
+impl Display for FilterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid filter at position {}: {}",
+            self.position, self.reason
+        )
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = Vec::new();
+        let expr = ExprParser::parse(s, &mut fields)?;
+        Ok(Filter { expr, fields })
+    }
+}
+
+impl Filter {
+    /// Whether `line` matches, or `None` if it isn't JSON
+    pub fn matches(&self, line: &[u8]) -> Option<bool> {
+        extract_fields(line, &self.fields).map(|fields| self.expr.eval(&fields))
+    }
+}
+
+/// Wraps another parser to skip lines which don't match a [Filter]. The
+/// filter runs first, so skipped lines are never parsed for their group.
+/// Lines which aren't JSON are left to the inner parser.
+pub struct FilterParser<P: Parser> {
+    inner: P,
+    filter: Option<Filter>,
+}
+
+impl<P: Parser> FilterParser<P> {
+    pub fn with_filter(inner: P, filter: Filter) -> Self {
+        Self {
+            inner,
+            filter: Some(filter),
+        }
+    }
+}
+
+impl<P: Parser> Parser for FilterParser<P> {
+    type Error = P::Error;
+
+    /// Without a filter every line is kept
+    fn new(group_key: &str) -> Self {
+        Self {
+            inner: P::new(group_key),
+            filter: None,
+        }
+    }
+
+    fn parse<'a>(&self, line: &'a [u8]) -> Result<LogLineMetadata<'a>, Self::Error> {
+        match self.filter.as_ref().and_then(|filter| filter.matches(line)) {
+            Some(false) => Ok(LogLineMetadata {
+                filter: LineFilter::Skip,
+                ..LogLineMetadata::new(Cow::Borrowed(&[]), line)
+            }),
+            _ => self.inner.parse(line),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StringParser;
+
+    fn matches(filter: &str, line: &str) -> bool {
+        let filter: Filter = filter.parse().unwrap();
+        filter.matches(line.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_filter_expressions() {
+        let line =
+            r#"{"level":"error","service":"api","code":503,"meta":{"tags":["a","b"]},"ok":false}"#;
+        assert!(matches(r#"level == "error" && service != "health""#, line));
+        assert!(!matches(r#"level == "error" && service == "health""#, line));
+        assert!(matches(r#"level == "info" || code >= 500"#, line));
+        assert!(matches(r#"!(code < 500) && ok == false"#, line));
+        assert!(matches(r#"service in ["web", "api"]"#, line));
+        assert!(matches(r#"level =~ "^err" && meta.tags[1] == "b""#, line));
+        assert!(matches(r#"exists(meta) && !exists(user)"#, line));
+        // missing fields and mismatched types are never equal
+        assert!(matches(r#"user != "bob" && !(code == "503")"#, line));
+        assert!(!matches(r#"user < "bob" || user >= "bob""#, line));
+    }
+
+    #[test]
+    fn test_invalid_filters() {
+        for filter in [
+            "",
+            "level ==",
+            "level = \"a\"",
+            "(level == 1",
+            "level == 1 level",
+            "level in []",
+            "level =~ \"(\"",
+            "level == \"a",
+            "a..b == 1",
+        ] {
+            assert!(filter.parse::<Filter>().is_err(), "{}", filter);
+        }
+        let err = "level == 1 &&".parse::<Filter>().unwrap_err();
+        assert_eq!(err.position, 13);
+    }
+
+    #[test]
+    fn test_filter_parser() {
+        let filter = "level == \"error\"".parse().unwrap();
+        let parser = FilterParser::with_filter(StringParser::new("type"), filter);
+        let kept = parser.parse(br#"{"type":"a","level":"error"}"#).unwrap();
+        assert!(matches!(kept.type_name, Cow::Borrowed(b"a")));
+        assert_eq!(kept.filter, LineFilter::Keep);
+        let skipped = parser.parse(br#"{"type":"a","level":"info"}"#).unwrap();
+        assert_eq!(skipped.filter, LineFilter::Skip);
+        assert!(parser.parse(b"not json").is_err());
+    }
+}
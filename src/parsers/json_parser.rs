@@ -1,25 +1,140 @@
+use crate::parsers::json_path::{JsonPath, PathSegment};
 use crate::{LogLineMetadata, Parser};
 
-use serde::Deserialize;
+use serde::de::{DeserializeSeed, Error, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::borrow::Cow;
+use std::fmt::Formatter;
 
-/// A convenience for using [serde_json]
-#[derive(Debug, PartialEq, Deserialize)]
-pub struct LogLine<'a> {
-    #[serde(borrow, rename = "type")]
-    pub typ: &'a str,
+/// A convenience for using [serde_json]. Walks a [JsonPath] into a
+/// document and pulls out the string at the end of it, borrowing from the
+/// line unless the string contains escapes.
+struct LogLine<'p> {
+    path: &'p [PathSegment],
 }
 
-pub struct JsonParser;
+impl<'de, 'p> DeserializeSeed<'de> for LogLine<'p> {
+    type Value = Cow<'de, str>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        match self.path.first() {
+            None => MaybeBorrowed::deserialize(deserializer).map(|s| s.0),
+            Some(PathSegment::Key(_)) => deserializer.deserialize_map(self),
+            Some(PathSegment::Index(_)) => deserializer.deserialize_seq(self),
+        }
+    }
+}
+
+impl<'de, 'p> Visitor<'de> for LogLine<'p> {
+    type Value = Cow<'de, str>;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        match self.path.first() {
+            Some(PathSegment::Key(key)) => write!(formatter, "an object with a field `{}`", key),
+            Some(PathSegment::Index(index)) => {
+                write!(formatter, "an array with an element at {}", index)
+            }
+            None => formatter.write_str("a string"),
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let target = match self.path.first() {
+            Some(PathSegment::Key(key)) => key.as_str(),
+            _ => return Err(A::Error::invalid_type(serde::de::Unexpected::Map, &self)),
+        };
+        let mut value = None;
+        while let Some(MaybeBorrowed(key)) = map.next_key()? {
+            if value.is_none() && key == target {
+                value = Some(map.next_value_seed(LogLine {
+                    path: &self.path[1..],
+                })?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        value.ok_or_else(|| A::Error::custom(format!("missing field `{}`", target)))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let target = match self.path.first() {
+            Some(PathSegment::Index(index)) => *index,
+            _ => return Err(A::Error::invalid_type(serde::de::Unexpected::Seq, &self)),
+        };
+        for _ in 0..target {
+            if seq.next_element::<IgnoredAny>()?.is_none() {
+                return Err(A::Error::custom(format!("missing element {}", target)));
+            }
+        }
+        let value = seq
+            .next_element_seed(LogLine {
+                path: &self.path[1..],
+            })?
+            .ok_or_else(|| A::Error::custom(format!("missing element {}", target)))?;
+        // the deserializer insists on the whole array being consumed
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(value)
+    }
+}
+
+/// Avoid allocating strings unless they contain escapes
+pub(crate) struct MaybeBorrowed<'de>(pub(crate) Cow<'de, str>);
+
+impl<'de> Deserialize<'de> for MaybeBorrowed<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CowVisitor;
+
+        impl<'de> Visitor<'de> for CowVisitor {
+            type Value = MaybeBorrowed<'de>;
+
+            fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+                formatter.write_str("a string")
+            }
+
+            fn visit_borrowed_str<E: Error>(self, v: &'de str) -> Result<Self::Value, E> {
+                Ok(MaybeBorrowed(Cow::Borrowed(v)))
+            }
+
+            fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(MaybeBorrowed(Cow::Owned(v.to_owned())))
+            }
+        }
+
+        deserializer.deserialize_str(CowVisitor)
+    }
+}
+
+pub struct JsonParser {
+    /// Where to find the value to group by
+    path: JsonPath,
+}
+
+impl JsonParser {
+    /// Group by a value nested anywhere in the document
+    pub fn with_path(path: JsonPath) -> Self {
+        Self { path }
+    }
+}
 
 impl Parser for JsonParser {
     type Error = serde_json::Error;
 
-    fn parse(line: &[u8]) -> Result<LogLineMetadata, Self::Error> {
-        let log_line: LogLine = serde_json::from_slice(line)?;
-        Ok(LogLineMetadata {
-            type_name: log_line.typ.as_bytes(),
-            bytes: line.len(),
-        })
+    fn new(group_key: &str) -> Self {
+        Self::with_path(JsonPath::from_key(group_key))
+    }
+
+    fn parse<'a>(&self, line: &'a [u8]) -> Result<LogLineMetadata<'a>, Self::Error> {
+        let mut deserializer = serde_json::Deserializer::from_slice(line);
+        let type_name = LogLine {
+            path: self.path.segments(),
+        }
+        .deserialize(&mut deserializer)?;
+        deserializer.end()?;
+        let type_name = match type_name {
+            Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+            Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+        };
+        Ok(LogLineMetadata::new(type_name, line))
     }
 }
 
@@ -27,22 +142,59 @@ impl Parser for JsonParser {
 mod tests {
     use super::*;
 
+    fn path_parser(path: &str) -> JsonParser {
+        JsonParser::with_path(path.parse().unwrap())
+    }
+
     #[test]
     fn test_parse_line() -> serde_json::Result<()> {
         let line = b"{\"type\":\"test\",\"value\":\"test\"}";
-        let metadata = JsonParser::parse(line)?;
-        assert_eq!(metadata.type_name, b"test");
+        let metadata = JsonParser::new("type").parse(line)?;
+        assert_eq!(&*metadata.type_name, b"test");
         assert_eq!(metadata.bytes, line.len());
         Ok(())
     }
 
     #[test]
     fn test_parse_invalid_line() {
+        let parser = JsonParser::new("type");
         let line = b"{\"typ\":\"test\",\"value\":\"test\"}";
-        assert!(JsonParser::parse(line).is_err());
+        assert!(parser.parse(line).is_err());
         let line = b"{\"type\":1,\"value\":\"test\"}";
-        assert!(JsonParser::parse(line).is_err());
+        assert!(parser.parse(line).is_err());
         let line = b"{\"type\":\"test\",\"value\":\"test\"";
-        assert!(JsonParser::parse(line).is_err());
+        assert!(parser.parse(line).is_err());
+    }
+
+    #[test]
+    fn test_parse_custom_key() -> serde_json::Result<()> {
+        let line = b"{\"type\":\"test\",\"ev\\u0065nt\":\"login\",\"nested\":{\"event\":1}}";
+        let metadata = JsonParser::new("event").parse(line)?;
+        assert_eq!(&*metadata.type_name, b"login");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_nested_path() -> serde_json::Result<()> {
+        let line = b"{\"meta\":{\"id\":[1],\"kind\":\"audit\"},\"kind\":\"top\"}";
+        let metadata = path_parser("meta.kind").parse(line)?;
+        assert!(matches!(metadata.type_name, Cow::Borrowed(b"audit")));
+        let line =
+            b"{\"request\":{\"calls\":[{\"method\":\"GET\"},{\"method\":\"P\\u004fST\"},{}]}}";
+        let metadata = path_parser("$.request.calls[1].method").parse(line)?;
+        assert!(matches!(metadata.type_name, Cow::Owned(_)));
+        assert_eq!(&*metadata.type_name, b"POST");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_nested_path_invalid() {
+        let parser = path_parser("request.calls[1].method");
+        let line = b"{\"request\":{\"calls\":[{\"method\":\"GET\"}]}}";
+        assert!(parser.parse(line).is_err());
+        let line = b"{\"request\":{\"calls\":{\"method\":\"GET\"}}}";
+        assert!(parser.parse(line).is_err());
+        let line = b"{\"request\":\"calls\"}";
+        assert!(parser.parse(line).is_err());
     }
 }
@@ -0,0 +1,168 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// One step into a JSON document
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    /// An object field
+    Key(String),
+    /// An array element
+    Index(usize),
+}
+
+/// A JSON-path style selector. Supports dotted keys, array indexes and
+/// bracket-quoted keys for names containing dots, with an optional leading
+/// `$`. E.g. `type`, `meta.kind`, `$.request.method`, `items[0].name` or
+/// `$["service.name"]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    segments: Vec<PathSegment>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct JsonPathError {
+    /// The byte offset in the path where parsing failed
+    position: usize,
+    reason: &'static str,
+}
+
+impl Display for JsonPathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid JSON path at position {}: {}",
+            self.position, self.reason
+        )
+    }
+}
+
+impl std::error::Error for JsonPathError {}
+
+impl JsonPath {
+    /// A path to a single top level key, taken literally
+    pub fn from_key(key: &str) -> Self {
+        JsonPath {
+            segments: vec![PathSegment::Key(key.to_owned())],
+        }
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        self.segments.as_slice()
+    }
+
+    /// If this path is a single top level key, return it. These can be
+    /// served by the faster string search parsers.
+    pub fn as_key(&self) -> Option<&str> {
+        match self.segments.as_slice() {
+            [PathSegment::Key(key)] => Some(key.as_str()),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = JsonPathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = s.as_bytes();
+        let error = |position, reason| Err(JsonPathError { position, reason });
+        let mut segments = Vec::new();
+        let mut i = 0;
+        // `$` on its own would select the entire document
+        if bytes.first() == Some(&b'$') {
+            i = 1;
+            match bytes.get(1) {
+                Some(b'.') => i = 2,
+                Some(b'[') => {}
+                _ => return error(1, "expected `.` or `[` after `$`"),
+            }
+        }
+        loop {
+            if bytes.get(i) == Some(&b'[') {
+                let close = match bytes[i..].iter().position(|&b| b == b']') {
+                    Some(close) => i + close,
+                    None => return error(i, "unclosed `[`"),
+                };
+                let inner = &s[i + 1..close];
+                let quoted = inner.len() >= 2
+                    && (inner.starts_with('"') && inner.ends_with('"')
+                        || inner.starts_with('\'') && inner.ends_with('\''));
+                if quoted {
+                    segments.push(PathSegment::Key(inner[1..inner.len() - 1].to_owned()));
+                } else {
+                    match inner.parse() {
+                        Ok(index) => segments.push(PathSegment::Index(index)),
+                        Err(_) => return error(i + 1, "expected an array index or quoted key"),
+                    }
+                }
+                i = close + 1;
+            } else {
+                let len = bytes[i..]
+                    .iter()
+                    .position(|&b| b == b'.' || b == b'[')
+                    .unwrap_or(bytes.len() - i);
+                if len == 0 {
+                    return error(i, "expected a key");
+                }
+                segments.push(PathSegment::Key(s[i..i + len].to_owned()));
+                i += len;
+            }
+            match bytes.get(i) {
+                None => break,
+                Some(b'.') => i += 1,
+                Some(b'[') => {}
+                Some(_) => return error(i, "expected `.` or `[`"),
+            }
+        }
+        Ok(JsonPath { segments })
+    }
+}
+
+impl Display for JsonPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "$")?;
+        for segment in self.segments.iter() {
+            match segment {
+                PathSegment::Key(key) if key.contains(['.', '[']) => write!(f, "[\"{}\"]", key)?,
+                PathSegment::Key(key) => write!(f, ".{}", key)?,
+                PathSegment::Index(index) => write!(f, "[{}]", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PathSegment::{Index, Key};
+
+    fn key(k: &str) -> PathSegment {
+        Key(k.to_owned())
+    }
+
+    #[test]
+    fn test_parse_paths() {
+        let path: JsonPath = "type".parse().unwrap();
+        assert_eq!(path.segments(), &[key("type")]);
+        assert_eq!(path.as_key(), Some("type"));
+        let path: JsonPath = "$.request.method".parse().unwrap();
+        assert_eq!(path.segments(), &[key("request"), key("method")]);
+        assert_eq!(path.as_key(), None);
+        let path: JsonPath = "items[0][12].name".parse().unwrap();
+        assert_eq!(
+            path.segments(),
+            &[key("items"), Index(0), Index(12), key("name")]
+        );
+        let path: JsonPath = "$[\"service.name\"]".parse().unwrap();
+        assert_eq!(path.as_key(), Some("service.name"));
+        assert_eq!(path.to_string(), "$[\"service.name\"]");
+    }
+
+    #[test]
+    fn test_parse_invalid_paths() {
+        for path in ["", "$", "a..b", "a.", "a[", "a[x]", "a[0]b", "$x"] {
+            assert!(path.parse::<JsonPath>().is_err(), "{}", path);
+        }
+    }
+}
@@ -1,30 +1,118 @@
-use std::fmt::Debug;
+use std::borrow::Cow;
+use std::fmt::{Debug, Display, Formatter};
 
+/// Any parser, chosen at runtime
+pub use boxed_parser::{BoxedParser, BoxedParserError};
 /// Available in benches
 pub use char_parser::CharParser;
+/// Group by several fields at once
+pub use composite_parser::{CompositeParser, CompositeParserError};
+/// Adds a field to count distinct values of to another parser
+pub use distinct_parser::DistinctParser;
+/// Adds the whole document to another parser
+pub use document_parser::DocumentParser;
+/// Skips lines which don't match an expression
+pub use filter::{Filter, FilterError, FilterParser};
 /// Available in benches
 pub use json_parser::JsonParser;
+pub use json_path::{JsonPath, JsonPathError, PathSegment};
 /// Available in benches
 pub use re_parser::RegexParser;
 /// The winner of best parser
 pub use string_parser::StringParser;
+/// Adds timestamps to another parser
+pub use timestamp_parser::{
+    format_timestamp, parse_timestamp, TimeWindow, TimestampParser, DEFAULT_TIMESTAMP_FIELDS,
+};
+/// Excludes lines by their type
+pub use type_filter::{TypeFilter, TypeFilterParser, TypePattern};
 
+mod boxed_parser;
 mod char_parser;
+mod composite_parser;
+mod distinct_parser;
+mod document_parser;
+mod filter;
 mod json_parser;
+mod json_path;
 mod re_parser;
 mod string_parser;
+mod timestamp_parser;
+mod type_filter;
+
+/// The field lines are grouped by unless told otherwise
+pub const DEFAULT_GROUP_KEY: &str = "type";
+
+/// Joins the values of a composite group key. This is the ASCII unit
+/// separator, which can't appear unescaped in a JSON string, though an
+/// escaped `\u001f` decodes to it, so [CompositeParser] rejects values
+/// containing it.
+pub const KEY_SEPARATOR: u8 = 0x1f;
 
 /// The successful result type of a line parse. References the string in the
-/// type name to avoid copying.
+/// type name to avoid copying where possible.
 #[derive(Debug, PartialEq)]
 pub struct LogLineMetadata<'a> {
-    pub type_name: &'a [u8],
+    /// Only owned if the value had to be decoded e.g. JSON escapes
+    pub type_name: Cow<'a, [u8]>,
+    /// The line itself, for keeping examples of
+    pub line: &'a [u8],
     pub bytes: usize,
+    /// Milliseconds since the Unix epoch, if the parser looks for them
+    pub timestamp: Option<i64>,
+    pub filter: LineFilter,
+    /// The value of a second field, whose distinct values are counted per
+    /// group, if the parser looks for one
+    pub distinct: Option<Cow<'a, [u8]>>,
+    /// The whole line, if the parser reads it in full
+    pub document: Option<serde_json::Value>,
+}
+
+impl<'a> LogLineMetadata<'a> {
+    /// A line of type `type_name` to be kept, with nothing else found
+    pub fn new(type_name: Cow<'a, [u8]>, line: &'a [u8]) -> Self {
+        Self {
+            type_name,
+            line,
+            bytes: line.len(),
+            timestamp: None,
+            filter: LineFilter::Keep,
+            distinct: None,
+            document: None,
+        }
+    }
+}
+
+/// The error of the parsers which look for the group key's string value
+#[derive(Debug)]
+pub struct NoTypeParsed;
+
+impl Display for NoTypeParsed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("no string value for the group key")
+    }
+}
+
+/// Whether a parsed line should be summarized
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineFilter {
+    Keep,
+    /// Count the line as filtered out rather than by its type
+    Skip,
+    /// Skip this line and stop reading, as nothing after it will be kept
+    /// either
+    Stop,
+    /// Count the line as excluded by its type
+    Exclude,
 }
 
 /// Take a line slice, return some metadata or a predefined error
-pub trait Parser {
-    /// The error of a parse operation
-    type Error: Debug;
-    fn parse(line: &[u8]) -> Result<LogLineMetadata, Self::Error>;
+pub trait Parser: Sized + Send + Sync + 'static {
+    /// The error of a parse operation, readable enough to show a user
+    type Error: Debug + Display;
+    /// Build a parser which groups lines by the field `group_key`. Anything
+    /// derived from the key (search needles, regexes) should be computed
+    /// here rather than per line.
+    fn new(group_key: &str) -> Self;
+    fn parse<'a>(&self, line: &'a [u8]) -> Result<LogLineMetadata<'a>, Self::Error>;
 }
@@ -1,27 +1,30 @@
+use crate::parsers::NoTypeParsed;
 use crate::{LogLineMetadata, Parser};
-use lazy_static::lazy_static;
 use regex::bytes::Regex;
+use std::borrow::Cow;
 
-lazy_static! {
-    static ref TYPE_RE: Regex = Regex::new(r#""type":\s*"([^"]+)"#).unwrap();
+pub struct RegexParser {
+    /// Captures the string value of the group key
+    key_re: Regex,
 }
 
-#[derive(Debug)]
-pub struct NoTypeParsed;
-
-pub struct RegexParser;
-
 impl Parser for RegexParser {
     type Error = NoTypeParsed;
 
-    fn parse(line: &[u8]) -> Result<LogLineMetadata, Self::Error> {
-        match TYPE_RE.captures(line) {
-            Some(caps) => Ok(LogLineMetadata {
-                // Unwrap is safe because the regex guarantees that the
-                // capture exists
-                type_name: caps.get(1).unwrap().as_bytes(),
-                bytes: line.len(),
-            }),
+    fn new(group_key: &str) -> Self {
+        // Unwrap is safe because the key is escaped
+        let key_re = Regex::new(&format!(r#""{}":\s*"([^"]+)"#, regex::escape(group_key))).unwrap();
+        Self { key_re }
+    }
+
+    fn parse<'a>(&self, line: &'a [u8]) -> Result<LogLineMetadata<'a>, Self::Error> {
+        match self.key_re.captures(line) {
+            // Unwrap is safe because the regex guarantees that the capture
+            // exists
+            Some(caps) => Ok(LogLineMetadata::new(
+                Cow::Borrowed(caps.get(1).unwrap().as_bytes()),
+                line,
+            )),
             None => Err(NoTypeParsed),
         }
     }
@@ -34,15 +37,23 @@ mod tests {
     #[test]
     fn test_regex_parse() {
         let line = b"{\"type\":\"some_complicated_TYPE123\",\"message\":\"Hello, world!\"}";
-        let result = RegexParser::parse(line).unwrap();
-        assert_eq!(result.type_name, b"some_complicated_TYPE123");
+        let result = RegexParser::new("type").parse(line).unwrap();
+        assert_eq!(&*result.type_name, b"some_complicated_TYPE123");
         assert_eq!(result.bytes, line.len());
     }
 
     #[test]
     fn test_regex_parse_no_type() {
         let line = b"{\"message\":\"Hello, world!\"}";
-        let result = RegexParser::parse(line);
+        let result = RegexParser::new("type").parse(line);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_regex_parse_custom_key() {
+        let line = b"{\"service.name\": \"billing\",\"type\":\"ignored\"}";
+        let result = RegexParser::new("service.name").parse(line).unwrap();
+        assert_eq!(&*result.type_name, b"billing");
+        assert!(RegexParser::new("service").parse(line).is_err());
+    }
 }
@@ -1,34 +1,46 @@
+use crate::parsers::NoTypeParsed;
 use crate::{LogLineMetadata, Parser};
+use std::borrow::Cow;
 
-const TYPE_STR: &[u8] = b"\"type\":";
 const QUOTE_STR: &[u8] = b"\"";
 
-#[derive(Debug)]
-pub struct NoTypeParsed;
-
-pub struct StringParser;
+pub struct StringParser {
+    /// The quoted key followed by a colon e.g. `"type":`
+    key_str: Vec<u8>,
+}
 
 impl Parser for StringParser {
     type Error = NoTypeParsed;
 
-    fn parse(line: &[u8]) -> Result<LogLineMetadata, Self::Error> {
-        find_subsequence(line, TYPE_STR)
+    fn new(group_key: &str) -> Self {
+        Self {
+            key_str: key_needle(group_key),
+        }
+    }
+
+    fn parse<'a>(&self, line: &'a [u8]) -> Result<LogLineMetadata<'a>, Self::Error> {
+        let key_str = self.key_str.as_slice();
+        find_subsequence(line, key_str)
             .and_then(|index| {
-                find_subsequence(&line[index + TYPE_STR.len()..], QUOTE_STR)
-                    .map(|i| i + index + TYPE_STR.len())
+                find_subsequence(&line[index + key_str.len()..], QUOTE_STR)
+                    .map(|i| i + index + key_str.len())
             })
             .and_then(|start_index| {
                 find_subsequence(&line[start_index + QUOTE_STR.len()..], QUOTE_STR)
                     .map(|rel_index| (start_index + QUOTE_STR.len(), start_index + rel_index))
             })
-            .map(|(start_index, end_index)| LogLineMetadata {
-                type_name: &line[start_index..=end_index],
-                bytes: line.len(),
+            .map(|(start_index, end_index)| {
+                LogLineMetadata::new(Cow::Borrowed(&line[start_index..=end_index]), line)
             })
             .ok_or(NoTypeParsed)
     }
 }
 
+/// The byte string we search for to find the start of a key's value
+pub fn key_needle(key: &str) -> Vec<u8> {
+    format!("\"{}\":", key).into_bytes()
+}
+
 pub fn find_subsequence(haystack: &[u8], needle: &[u8]) -> Option<usize> {
     haystack
         .windows(needle.len())
@@ -42,16 +54,24 @@ mod tests {
     #[test]
     fn test_string_parse() {
         let line = b"{\"type\":\"some_complicated_TYPE123\",\"message\":\"Hello, world!\"}";
-        let result = StringParser::parse(line).unwrap();
-        println!("IM {}", String::from_utf8_lossy(result.type_name));
-        assert_eq!(result.type_name, b"some_complicated_TYPE123");
+        let result = StringParser::new("type").parse(line).unwrap();
+        println!("IM {}", String::from_utf8_lossy(&result.type_name));
+        assert_eq!(&*result.type_name, b"some_complicated_TYPE123");
         assert_eq!(result.bytes, line.len());
     }
 
     #[test]
     fn test_string_parse_no_type() {
         let line = b"{\"message\":\"Hello, world!\"}";
-        let result = StringParser::parse(line);
+        let result = StringParser::new("type").parse(line);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_string_parse_custom_key() {
+        let line = b"{\"type\":\"ignored\",\"event\":\"login\",\"message\":\"Hello, world!\"}";
+        let result = StringParser::new("event").parse(line).unwrap();
+        assert_eq!(&*result.type_name, b"login");
+        assert!(StringParser::new("level").parse(line).is_err());
+    }
 }
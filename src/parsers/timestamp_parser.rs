@@ -0,0 +1,289 @@
+use crate::parsers::string_parser::{find_subsequence, key_needle};
+use crate::{LineFilter, LogLineMetadata, Parser};
+
+/// The fields searched for a timestamp unless told otherwise, in order
+pub const DEFAULT_TIMESTAMP_FIELDS: [&str; 2] = ["ts", "@timestamp"];
+
+/// Only keep lines from `since` up to, but not including, `until`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeWindow {
+    /// Milliseconds since the epoch
+    pub since: Option<i64>,
+    /// Milliseconds since the epoch
+    pub until: Option<i64>,
+    /// The input is in time order, so nothing after the first line past
+    /// `until` can be in the window
+    pub ordered: bool,
+}
+
+impl TimeWindow {
+    pub fn new(since: Option<i64>, until: Option<i64>, ordered: bool) -> Self {
+        Self {
+            since,
+            until,
+            ordered,
+        }
+    }
+
+    /// Lines without a timestamp can't be placed, so they're skipped
+    pub fn filter(&self, timestamp: Option<i64>) -> LineFilter {
+        match timestamp {
+            None => LineFilter::Skip,
+            Some(ts) if matches!(self.since, Some(since) if ts < since) => LineFilter::Skip,
+            Some(ts) if matches!(self.until, Some(until) if ts >= until) => {
+                if self.ordered {
+                    LineFilter::Stop
+                } else {
+                    LineFilter::Skip
+                }
+            }
+            Some(_) => LineFilter::Keep,
+        }
+    }
+}
+
+/// Wraps another parser to also pull out each line's timestamp. Lines
+/// without a timestamp we understand still parse, they just have none.
+pub struct TimestampParser<P: Parser> {
+    inner: P,
+    /// The needle for each field, e.g. `"ts":`
+    fields: Vec<Vec<u8>>,
+    window: Option<TimeWindow>,
+}
+
+impl<P: Parser> TimestampParser<P> {
+    /// Look for the timestamp in each of `fields` in turn
+    pub fn with_fields<S: AsRef<str>>(inner: P, fields: &[S]) -> Self {
+        Self {
+            inner,
+            fields: fields.iter().map(|f| key_needle(f.as_ref())).collect(),
+            window: None,
+        }
+    }
+
+    /// Filter out lines outside of `window`
+    pub fn within(mut self, window: TimeWindow) -> Self {
+        self.window = Some(window);
+        self
+    }
+}
+
+impl<P: Parser> Parser for TimestampParser<P> {
+    type Error = P::Error;
+
+    fn new(group_key: &str) -> Self {
+        Self::with_fields(P::new(group_key), &DEFAULT_TIMESTAMP_FIELDS)
+    }
+
+    fn parse<'a>(&self, line: &'a [u8]) -> Result<LogLineMetadata<'a>, Self::Error> {
+        let mut metadata = self.inner.parse(line)?;
+        metadata.timestamp = self
+            .fields
+            .iter()
+            .find_map(|needle| find_timestamp(line, needle));
+        if let Some(window) = &self.window {
+            metadata.filter = window.filter(metadata.timestamp);
+        }
+        Ok(metadata)
+    }
+}
+
+/// The value following `needle`, either a quoted string or a bare number
+fn find_timestamp(line: &[u8], needle: &[u8]) -> Option<i64> {
+    let start = find_subsequence(line, needle)? + needle.len();
+    let value = &line[start..];
+    let value = &value[value.iter().position(|b| !b.is_ascii_whitespace())?..];
+    let value = match value.split_first() {
+        Some((b'"', rest)) => &rest[..find_subsequence(rest, b"\"")?],
+        _ => {
+            let end = value
+                .iter()
+                .position(|b| !(b.is_ascii_digit() || *b == b'-'))
+                .unwrap_or(value.len());
+            &value[..end]
+        }
+    };
+    parse_timestamp(std::str::from_utf8(value).ok()?)
+}
+
+/// Milliseconds since the Unix epoch, from either an RFC 3339 date or a
+/// number of milliseconds
+pub fn parse_timestamp(value: &str) -> Option<i64> {
+    match value.parse::<i64>() {
+        Ok(millis) => Some(millis),
+        Err(_) => parse_rfc3339(value.as_bytes()),
+    }
+}
+
+fn digits(bytes: &[u8]) -> Option<i64> {
+    if bytes.is_empty() || !bytes.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    Some(
+        bytes
+            .iter()
+            .fold(0, |n, digit| n * 10 + i64::from(digit - b'0')),
+    )
+}
+
+/// e.g. `2022-03-01T12:30:00.250+01:00`. A space is accepted in place of
+/// the `T`, and anything finer than a millisecond is ignored.
+fn parse_rfc3339(s: &[u8]) -> Option<i64> {
+    if s.len() < 20
+        || s[4] != b'-'
+        || s[7] != b'-'
+        || !matches!(s[10], b'T' | b't' | b' ')
+        || s[13] != b':'
+        || s[16] != b':'
+    {
+        return None;
+    }
+    let (year, month, day) = (digits(&s[0..4])?, digits(&s[5..7])?, digits(&s[8..10])?);
+    let (hour, minute, second) = (
+        digits(&s[11..13])?,
+        digits(&s[14..16])?,
+        digits(&s[17..19])?,
+    );
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+    let mut rest = &s[19..];
+    let mut millis = 0;
+    if let Some((b'.', fraction)) = rest.split_first() {
+        let len = fraction
+            .iter()
+            .position(|b| !b.is_ascii_digit())
+            .unwrap_or(fraction.len());
+        let mut padded = [b'0'; 3];
+        for (digit, slot) in fraction[..len].iter().zip(padded.iter_mut()) {
+            *slot = *digit;
+        }
+        millis = digits(&padded)?;
+        rest = &fraction[len..];
+    }
+    let offset_minutes = match rest {
+        [b'Z'] | [b'z'] => 0,
+        [sign @ (b'+' | b'-'), hours @ .., b':', m1, m2] if hours.len() == 2 => {
+            let offset = digits(hours)? * 60 + digits(&[*m1, *m2])?;
+            if *sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second
+        - offset_minutes * 60;
+    Some(seconds * 1000 + millis)
+}
+
+/// Days since 1970-01-01 in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The inverse of [days_from_civil]
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Format milliseconds since the epoch as an RFC 3339 date in UTC, to the
+/// second
+pub fn format_timestamp(millis: i64) -> String {
+    let seconds = millis.div_euclid(1000);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    let time = seconds.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StringParser;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1646137800000"), Some(1_646_137_800_000));
+        assert_eq!(
+            parse_timestamp("2022-03-01T12:30:00Z"),
+            Some(1_646_137_800_000)
+        );
+        assert_eq!(
+            parse_timestamp("2022-03-01 13:30:00.25+01:00"),
+            Some(1_646_137_800_250)
+        );
+        assert_eq!(parse_timestamp("1969-12-31T23:59:59Z"), Some(-1000));
+        assert_eq!(parse_timestamp("2022-13-01T12:30:00Z"), None);
+        assert_eq!(parse_timestamp("2022-03-01T12:30:00"), None);
+        assert_eq!(parse_timestamp("yesterday"), None);
+        assert_eq!(format_timestamp(1_646_137_800_250), "2022-03-01T12:30:00Z");
+        assert_eq!(format_timestamp(-1000), "1969-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn test_timestamp_parser() {
+        let parser = TimestampParser::<StringParser>::new("type");
+        let line = b"{\"type\":\"a\",\"@timestamp\":\"2022-03-01T12:30:00Z\"}";
+        let metadata = parser.parse(line).unwrap();
+        assert_eq!(&*metadata.type_name, b"a");
+        assert_eq!(metadata.timestamp, Some(1_646_137_800_000));
+        let line = b"{\"type\":\"a\",\"ts\": 1646137800000}";
+        assert_eq!(
+            parser.parse(line).unwrap().timestamp,
+            Some(1_646_137_800_000)
+        );
+        let line = b"{\"type\":\"a\",\"ts\":\"soon\"}";
+        assert_eq!(parser.parse(line).unwrap().timestamp, None);
+        assert!(parser.parse(b"{\"ts\":1}").is_err());
+    }
+
+    #[test]
+    fn test_time_window() {
+        let parser = TimestampParser::<StringParser>::new("type").within(TimeWindow::new(
+            Some(10),
+            Some(20),
+            true,
+        ));
+        let filter = |line: &[u8]| parser.parse(line).unwrap().filter;
+        assert_eq!(filter(b"{\"type\":\"a\",\"ts\":9}"), LineFilter::Skip);
+        assert_eq!(filter(b"{\"type\":\"a\",\"ts\":10}"), LineFilter::Keep);
+        assert_eq!(filter(b"{\"type\":\"a\",\"ts\":20}"), LineFilter::Stop);
+        assert_eq!(filter(b"{\"type\":\"a\"}"), LineFilter::Skip);
+        let unordered = TimeWindow::new(None, Some(20), false);
+        assert_eq!(unordered.filter(Some(20)), LineFilter::Skip);
+        assert_eq!(unordered.filter(Some(-5)), LineFilter::Keep);
+    }
+}
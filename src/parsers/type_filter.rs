@@ -0,0 +1,140 @@
+use crate::{LineFilter, LogLineMetadata, Parser, KEY_SEPARATOR};
+use regex::bytes::Regex;
+use std::str::FromStr;
+
+/// Matches the value of a group key. `/.../` is a regex, anything with a
+/// `*` or `?` is a glob, and anything else must match exactly.
+#[derive(Debug, Clone)]
+pub enum TypePattern {
+    Literal(Vec<u8>),
+    /// A glob, compiled to an anchored regex
+    Glob(Regex),
+    Regex(Regex),
+}
+
+impl TypePattern {
+    pub fn matches(&self, value: &[u8]) -> bool {
+        match self {
+            TypePattern::Literal(literal) => literal == value,
+            TypePattern::Glob(re) | TypePattern::Regex(re) => re.is_match(value),
+        }
+    }
+}
+
+impl FromStr for TypePattern {
+    type Err = regex::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() >= 2 && s.starts_with('/') && s.ends_with('/') {
+            return Ok(TypePattern::Regex(Regex::new(&s[1..s.len() - 1])?));
+        }
+        if !s.contains(['*', '?']) {
+            return Ok(TypePattern::Literal(s.as_bytes().to_vec()));
+        }
+        let mut re = String::from("^");
+        for c in s.chars() {
+            match c {
+                '*' => re.push_str(".*"),
+                '?' => re.push('.'),
+                c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        re.push('$');
+        Ok(TypePattern::Glob(Regex::new(&re)?))
+    }
+}
+
+/// Which types to summarize. A line is kept if its type matches any of
+/// `include`, or there are none, and matches none of `exclude`. With a
+/// composite group key, a pattern matches if it matches any field.
+#[derive(Debug, Clone, Default)]
+pub struct TypeFilter {
+    pub include: Vec<TypePattern>,
+    pub exclude: Vec<TypePattern>,
+}
+
+impl TypeFilter {
+    pub fn new(include: Vec<TypePattern>, exclude: Vec<TypePattern>) -> Self {
+        Self { include, exclude }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn allows(&self, type_name: &[u8]) -> bool {
+        let any_field = |pattern: &TypePattern| {
+            type_name
+                .split(|&b| b == KEY_SEPARATOR)
+                .any(|field| pattern.matches(field))
+        };
+        (self.include.is_empty() || self.include.iter().any(any_field))
+            && !self.exclude.iter().any(any_field)
+    }
+}
+
+/// Wraps another parser to mark lines of unwanted types as excluded, so
+/// they're counted apart from the rest
+pub struct TypeFilterParser<P: Parser> {
+    inner: P,
+    filter: TypeFilter,
+}
+
+impl<P: Parser> TypeFilterParser<P> {
+    pub fn with_filter(inner: P, filter: TypeFilter) -> Self {
+        Self { inner, filter }
+    }
+}
+
+impl<P: Parser> Parser for TypeFilterParser<P> {
+    type Error = P::Error;
+
+    /// Without any patterns every type is kept
+    fn new(group_key: &str) -> Self {
+        Self::with_filter(P::new(group_key), TypeFilter::default())
+    }
+
+    fn parse<'a>(&self, line: &'a [u8]) -> Result<LogLineMetadata<'a>, Self::Error> {
+        let mut metadata = self.inner.parse(line)?;
+        if metadata.filter == LineFilter::Keep && !self.filter.allows(&metadata.type_name) {
+            metadata.filter = LineFilter::Exclude;
+        }
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StringParser;
+
+    fn patterns(patterns: &[&str]) -> Vec<TypePattern> {
+        patterns.iter().map(|p| p.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_type_patterns() {
+        let filter = TypeFilter::new(
+            patterns(&["order.*", "/^user_(created|deleted)$/", "payment"]),
+            patterns(&["*.heartbeat", "order.?"]),
+        );
+        assert!(filter.allows(b"order.placed"));
+        assert!(filter.allows(b"user_created"));
+        assert!(filter.allows(b"payment"));
+        assert!(!filter.allows(b"payments"));
+        assert!(!filter.allows(b"user_updated"));
+        assert!(!filter.allows(b"order.heartbeat"));
+        assert!(!filter.allows(b"order.x"));
+        assert!(filter.allows(b"api\x1fpayment"));
+        assert!("/(/".parse::<TypePattern>().is_err());
+    }
+
+    #[test]
+    fn test_type_filter_parser() {
+        let filter = TypeFilter::new(vec![], patterns(&["heartbeat"]));
+        let parser = TypeFilterParser::with_filter(StringParser::new("type"), filter);
+        let filter = |line: &[u8]| parser.parse(line).unwrap().filter;
+        assert_eq!(filter(b"{\"type\":\"heartbeat\"}"), LineFilter::Exclude);
+        assert_eq!(filter(b"{\"type\":\"order\"}"), LineFilter::Keep);
+    }
+}
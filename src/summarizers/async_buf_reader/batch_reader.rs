@@ -4,64 +4,217 @@ use tokio::io::{AsyncRead, AsyncReadExt};
 
 /// Custom reader which streams a fixed size from the reader and truncates
 /// any trailing data past the delimiter, starting the next batch with it,
-/// if present.
+/// if present. If there's no delimiter at all the buffer keeps growing
+/// until there is, or until the line is longer than the maximum, at which
+/// point the line is skipped.
 pub struct AsyncBatchReader<R: AsyncRead + Unpin + Send + Sync> {
     reader: R,
     remainder_buffer: Vec<u8>,
     buffer_size: usize,
     delimiter: u8,
+    /// Lines longer than this are skipped rather than buffered
+    max_line_length: usize,
+    /// The sequence number of the next batch
+    sequence: usize,
+    /// Wait for more data at the end of the reader rather than treating
+    /// a trailing line without a delimiter as complete
+    follow: bool,
+    /// The number of bytes read from the reader so far
+    position: u64,
+    /// The length so far of an oversized line which hit the end of the
+    /// reader while being skipped in follow mode
+    skipping: Option<usize>,
+}
+
+/// A run of whole lines from the reader
+#[derive(Debug)]
+pub struct Batch {
+    /// Batches are numbered from zero in the order they were read
+    pub sequence: usize,
+    /// The number of lines in `data`
+    pub lines: usize,
+    /// Empty if this batch stands in for an oversized line
+    pub data: Vec<u8>,
+    /// The length of the line this batch stands in for, if it was over
+    /// the maximum line length. The line itself is thrown away.
+    pub oversized: Option<usize>,
 }
 
 impl<R: AsyncRead + Unpin + Send + Sync> AsyncBatchReader<R> {
-    pub fn new(reader: R, buffer_size: usize, delimiter: u8) -> AsyncBatchReader<R> {
+    pub fn new(
+        reader: R,
+        buffer_size: usize,
+        delimiter: u8,
+        max_line_length: usize,
+    ) -> AsyncBatchReader<R> {
         AsyncBatchReader {
             reader,
             remainder_buffer: Vec::with_capacity(buffer_size),
             buffer_size,
             delimiter,
+            max_line_length,
+            sequence: 0,
+            follow: false,
+            position: 0,
+            skipping: None,
+        }
+    }
+
+    /// Hold on to a trailing partial line at the end of the reader, so
+    /// reading can carry on once more data has been appended
+    pub fn following(mut self) -> Self {
+        self.follow = true;
+        self
+    }
+
+    /// The number of bytes read from the underlying reader
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Forget any partially read line, e.g. after seeking the reader back
+    /// to the start. Batches carry on being numbered from where they were.
+    pub fn reset(&mut self) {
+        self.remainder_buffer.clear();
+        self.position = 0;
+        self.skipping = None;
+    }
+
+    /// The reader won't grow any more, so hand over whatever's been held
+    /// back in follow mode as a final line
+    pub fn finish(&mut self) -> Option<Batch> {
+        if let Some(length) = self.skipping.take() {
+            return Some(self.next_batch(vec![], Some(length)));
+        }
+        if self.remainder_buffer.is_empty() {
+            return None;
+        }
+        let data = std::mem::take(&mut self.remainder_buffer);
+        Some(self.next_batch(data, None))
+    }
+
+    fn next_batch(&mut self, data: Vec<u8>, oversized: Option<usize>) -> Batch {
+        let lines = match oversized {
+            Some(_) => 1,
+            None => count_lines(data.as_slice(), self.delimiter),
+        };
+        let batch = Batch {
+            sequence: self.sequence,
+            lines,
+            data,
+            oversized,
+        };
+        self.sequence += 1;
+        batch
+    }
+
+    /// Throw away the rest of a line which has already had `length` bytes
+    /// read, keeping anything after its delimiter for the next batch.
+    /// Returns the full length of the line, or `None` if the reader ran
+    /// out first in follow mode.
+    async fn skip_line(&mut self, mut length: usize) -> io::Result<Option<usize>> {
+        let mut buffer = Vec::with_capacity(self.buffer_size);
+        loop {
+            buffer.clear();
+            let read = (&mut self.reader)
+                .take(self.buffer_size as u64)
+                .read_to_end(&mut buffer)
+                .await?;
+            self.position += read as u64;
+            if read == 0 {
+                if self.follow {
+                    self.skipping = Some(length);
+                    return Ok(None);
+                }
+                return Ok(Some(length));
+            }
+            match buffer.iter().position(|&b| b == self.delimiter) {
+                Some(delim) => {
+                    self.remainder_buffer = buffer[delim + 1..].to_vec();
+                    return Ok(Some(length + delim + 1));
+                }
+                None => length += read,
+            }
         }
     }
 }
 #[async_trait]
 pub trait AsyncBatchRead {
-    async fn read_batch(&mut self) -> io::Result<Option<Vec<u8>>>;
+    async fn read_batch(&mut self) -> io::Result<Option<Batch>>;
 }
 
 #[async_trait]
 impl<R: AsyncRead + Unpin + Send + Sync> AsyncBatchRead for AsyncBatchReader<R> {
-    async fn read_batch(&mut self) -> io::Result<Option<Vec<u8>>> {
+    async fn read_batch(&mut self) -> io::Result<Option<Batch>> {
+        if let Some(length) = self.skipping.take() {
+            return Ok(self
+                .skip_line(length)
+                .await?
+                .map(|length| self.next_batch(vec![], Some(length))));
+        }
         let mut buffer = Vec::with_capacity(self.buffer_size);
         std::mem::swap(&mut buffer, &mut self.remainder_buffer);
-        (&mut self.reader)
-            .take((self.buffer_size - buffer.len()) as u64)
-            .read_to_end(&mut buffer)
-            .await?;
-        if buffer.is_empty() {
-            return Ok(None);
+        // everything before this has been checked for a delimiter
+        let mut searched = 0;
+        loop {
+            // fill the buffer, or grow it by another batch if the line
+            // didn't fit
+            let to_read = if buffer.len() < self.buffer_size {
+                self.buffer_size - buffer.len()
+            } else {
+                self.buffer_size
+            };
+            let read = (&mut self.reader)
+                .take(to_read as u64)
+                .read_to_end(&mut buffer)
+                .await?;
+            self.position += read as u64;
+            if let Some(delim) = last_delim(&buffer[searched..], self.delimiter) {
+                let delim = searched + delim;
+                self.remainder_buffer = buffer[delim + 1..].to_vec();
+                buffer.truncate(delim + 1);
+                return Ok(Some(self.next_batch(buffer, None)));
+            }
+            if read == 0 {
+                // end of the file, which may not end with a delimiter
+                if buffer.is_empty() {
+                    return Ok(None);
+                }
+                // unless more might be on its way
+                if self.follow {
+                    self.remainder_buffer = buffer;
+                    return Ok(None);
+                }
+                return Ok(Some(self.next_batch(buffer, None)));
+            }
+            searched = buffer.len();
+            if buffer.len() > self.max_line_length {
+                return Ok(self
+                    .skip_line(buffer.len())
+                    .await?
+                    .map(|length| self.next_batch(vec![], Some(length))));
+            }
         }
-        // TODO: don't unwrap - grow buffer
-        //  in practice just choose a decent buffer size (if your lines are
-        //  over 1MB you don't deserve a summary :P)
-        let delim = last_delim(buffer.as_slice(), self.delimiter).unwrap();
-        self.remainder_buffer = buffer[delim + 1..].to_vec();
-        buffer.truncate(delim + 1);
-        Ok(Some(buffer))
+    }
+}
+
+/// The number of lines in a buffer, counting a trailing line without a
+/// delimiter
+fn count_lines(buf: &[u8], delim: u8) -> usize {
+    let delims = buf.iter().filter(|&&b| b == delim).count();
+    match buf.last() {
+        Some(&last) if last != delim => delims + 1,
+        _ => delims,
     }
 }
 
 /// Return the last occurrence of a delimiter in a buffer
 fn last_delim(buf: &[u8], delim: u8) -> Option<usize> {
-    let mut i = buf.len() - 1;
-    loop {
-        if buf[i] == delim {
-            return Some(i);
-        }
-        if i == 0 {
-            break;
-        }
-        i -= 1;
-    }
-    None
+    buf.iter().rposition(|&b| b == delim)
 }
 
 #[cfg(test)]
@@ -76,11 +229,103 @@ mod tests {
         assert_eq!(last_delim(buf, b'\n').unwrap(), 0);
     }
 
+    #[test]
+    fn test_count_lines() {
+        assert_eq!(count_lines(b"", b'\n'), 0);
+        assert_eq!(count_lines(b"a\nb\n", b'\n'), 2);
+        assert_eq!(count_lines(b"a\n\nb", b'\n'), 3);
+    }
+
+    #[tokio::test]
+    async fn test_read_batch_sequence() -> io::Result<()> {
+        let mut reader = AsyncBatchReader::new(&b"one\ntwo\nthree\n"[..], 9, b'\n', usize::MAX);
+        let batch = reader.read_batch().await?.unwrap();
+        assert_eq!((batch.sequence, batch.lines), (0, 2));
+        assert_eq!(batch.data, b"one\ntwo\n");
+        let batch = reader.read_batch().await?.unwrap();
+        assert_eq!((batch.sequence, batch.lines), (1, 1));
+        assert_eq!(batch.data, b"three\n");
+        assert!(reader.read_batch().await?.is_none());
+        Ok(())
+    }
+
     #[test]
     fn test_last_delim_non_existant() {
         let buf = b"Here's some text";
         assert!(last_delim(buf, b'\n').is_none());
         let buf = b"Here's some text\\nHere's some more";
         assert!(last_delim(buf, b'\n').is_none());
+        assert!(last_delim(b"", b'\n').is_none());
+    }
+
+    async fn read_all<R: AsyncRead + Unpin + Send + Sync>(
+        mut reader: AsyncBatchReader<R>,
+    ) -> io::Result<Vec<Batch>> {
+        let mut batches = vec![];
+        while let Some(batch) = reader.read_batch().await? {
+            batches.push(batch);
+        }
+        Ok(batches)
+    }
+
+    #[tokio::test]
+    async fn test_read_batch_long_line() -> io::Result<()> {
+        let data = b"a\nthis line is longer than the batch\nb\nc";
+        let batches = read_all(AsyncBatchReader::new(&data[..], 4, b'\n', usize::MAX)).await?;
+        let lines: Vec<&[u8]> = batches.iter().map(|b| b.data.as_slice()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                &b"a\n"[..],
+                &b"this line is longer than the batch\n"[..],
+                &b"b\n"[..],
+                &b"c"[..]
+            ]
+        );
+        assert_eq!(batches.iter().map(|b| b.lines).sum::<usize>(), 4);
+        assert!(batches.iter().all(|b| b.oversized.is_none()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_batch_oversized_line() -> io::Result<()> {
+        let data = b"a\nthis line is longer than the maximum\nb\nthis one too";
+        let batches = read_all(AsyncBatchReader::new(&data[..], 4, b'\n', 10)).await?;
+        let summary: Vec<(usize, Option<usize>, &[u8])> = batches
+            .iter()
+            .map(|b| (b.lines, b.oversized, b.data.as_slice()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, None, &b"a\n"[..]),
+                (1, Some(37), &b""[..]),
+                (1, None, &b"b\n"[..]),
+                (1, Some(12), &b""[..]),
+            ]
+        );
+        assert_eq!(batches.last().unwrap().sequence, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_follow_holds_partial_lines() -> io::Result<()> {
+        use std::io::Write;
+        let path = std::env::temp_dir().join(format!("log_parser_follow_{}", std::process::id()));
+        std::fs::write(&path, "a\nb")?;
+        let file = tokio::fs::File::open(&path).await?;
+        let mut reader = AsyncBatchReader::new(file, 16, b'\n', 8).following();
+        assert_eq!(reader.read_batch().await?.unwrap().data, b"a\n");
+        assert!(reader.read_batch().await?.is_none());
+        let mut writer = std::fs::OpenOptions::new().append(true).open(&path)?;
+        writer.write_all(b"\nc\nthis line is too long")?;
+        assert_eq!(reader.read_batch().await?.unwrap().data, b"b\nc\n");
+        assert!(reader.read_batch().await?.is_none());
+        writer.write_all(b" still\nd\n")?;
+        let batch = reader.read_batch().await?.unwrap();
+        assert_eq!(batch.oversized, Some(28));
+        assert_eq!(reader.read_batch().await?.unwrap().data, b"d\n");
+        assert_eq!(reader.position(), 36);
+        std::fs::remove_file(path)
     }
 }
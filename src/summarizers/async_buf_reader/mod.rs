@@ -1,22 +1,32 @@
-use crate::summarizers::async_buf_reader::batch_reader::{AsyncBatchRead, AsyncBatchReader};
+use crate::summarizers::async_buf_reader::batch_reader::{AsyncBatchRead, AsyncBatchReader, Batch};
 use crate::summarizers::async_buf_reader::parser_error::AsyncBatchReaderParserError;
-use crate::{Parser, Summarizer, Summary, DEFAULT_DELIMITER};
-use byte_lines::ByteLinesExt;
+use crate::summarizers::byte_lines::ByteLinesExt;
+use crate::summarizers::decompress::decompress_async;
+use crate::summarizers::options::SummaryOptions;
+use crate::summarizers::rejects::{RejectFile, Rejected};
+use crate::{ErrorSummary, LineFilter, Parser, Summarizer, Summary, DEFAULT_DELIMITER, STDIN_PATH};
 pub use error::AsyncBufReaderSummarizerError;
+use std::collections::BTreeMap;
+use std::io::SeekFrom;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncSeekExt};
 
 use tokio::runtime::Runtime;
 use tokio::task::JoinHandle;
 
 mod batch_reader;
-/// A utility for iterating byte arrays over delimiters.
-mod byte_lines;
 mod error;
 mod parser_error;
 
 pub const DEFAULT_BATCH_SIZE: usize = 1_048_576;
 pub const DEFAULT_REDUCER_CHANNEL_SIZE: usize = 1024;
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 67_108_864;
+pub const DEFAULT_FOLLOW_INTERVAL_MS: u64 = 1000;
 
+#[derive(Clone)]
 pub struct AsyncBufReaderSummarizer {
     /// The maximum number of messages in the reducer channel before it
     /// blocks.
@@ -25,6 +35,234 @@ pub struct AsyncBufReaderSummarizer {
     batch_size: usize,
     /// The character to split on
     delim: u8,
+    /// Lines longer than this are registered as errors without ever being
+    /// held in memory in full
+    max_line_length: usize,
+    /// What to keep, and where to write lines which fail to parse
+    options: SummaryOptions,
+}
+
+/// What a mapper sends to the reducer
+struct MappedBatch<E: ErrorSummary> {
+    /// See [batch_reader::Batch]
+    sequence: usize,
+    lines: usize,
+    summary: Summary<E>,
+    /// Numbered relative to the batch, like the errors
+    rejected: Vec<Rejected>,
+    /// A line told us to stop reading
+    stopped: bool,
+}
+
+/// How each batch is summarized, copied in to every mapper task
+#[derive(Clone)]
+struct Mapper {
+    delim: u8,
+    max_line_length: usize,
+    /// Already seeded for this input
+    options: SummaryOptions,
+}
+
+impl Mapper {
+    /// Parse every line in a batch
+    fn map<P: Parser>(&self, batch: Batch, parser: &P) -> MappedBatch<AsyncBatchReaderParserError> {
+        // each batch makes its own random choices
+        let mut summary = self
+            .options
+            .for_part(&(batch.sequence as u64).to_le_bytes())
+            .new_summary();
+        let rejecting = self.options.rejects().is_some();
+        // errors are numbered relative to the batch
+        let mut rejected = Vec::new();
+        if let Some(length) = batch.oversized {
+            summary.register_error(0);
+            if rejecting {
+                // the line's gone, so all that's left to write is where it was
+                let reason = format!("line of {} bytes was skipped", length);
+                rejected.push(Rejected::new(0, &reason, &[]));
+            }
+        }
+        let mut stopped = false;
+        for (i, line) in batch.data.byte_lines(self.delim).enumerate() {
+            if line.len() > self.max_line_length {
+                summary.register_error(i);
+                if rejecting {
+                    let reason = format!("line longer than {} bytes", self.max_line_length);
+                    rejected.push(Rejected::new(i, &reason, line));
+                }
+                continue;
+            }
+            match parser.parse(line) {
+                Ok(meta) => {
+                    summary.accumulate(&meta);
+                    if meta.filter == LineFilter::Stop {
+                        stopped = true;
+                        break;
+                    }
+                }
+                Err(err) => {
+                    summary.register_error(i);
+                    if rejecting {
+                        rejected.push(Rejected::new(i, &err, line));
+                    }
+                }
+            }
+        }
+        MappedBatch {
+            sequence: batch.sequence,
+            lines: batch.lines,
+            summary,
+            rejected,
+            stopped,
+        }
+    }
+}
+
+/// Rather complex map reduce algorithm to read in batches and delegate
+/// each batch to a new tokio task (mapper), which sends its summary down a
+/// channel of `channel_size` to be reduced as it arrives. Reads until the
+/// reader runs out, or a line tells us to stop, then hands the reader
+/// back along with whether it was stopped.
+async fn map_reduce<R, P>(
+    reader: AsyncBatchReader<R>,
+    // shared between all of the mappers
+    parser: &Arc<P>,
+    reducer: &mut Reducer,
+    mapper: &Mapper,
+    channel_size: usize,
+) -> Result<(AsyncBatchReader<R>, bool), AsyncBufReaderSummarizerError>
+where
+    R: AsyncRead + Unpin + Send + Sync + 'static,
+    P: Parser,
+{
+    // channels from mappers to reducers
+    let (tx, mut rx) =
+        tokio::sync::mpsc::channel::<MappedBatch<AsyncBatchReaderParserError>>(channel_size);
+
+    // set by a mapper once there's no point reading any further. Batches
+    // already read are still mapped, but the reducer drops them.
+    let stop = Arc::new(AtomicBool::new(false));
+
+    // the reader task which spawns the mappers
+    let parser = parser.clone();
+    let mapper = mapper.clone();
+    let reader_handle: JoinHandle<Result<_, AsyncBufReaderSummarizerError>> =
+        tokio::spawn(async move {
+            let mut reader = reader;
+            let mut handles = Vec::new();
+            while !stop.load(Ordering::Relaxed) {
+                let batch = match reader.read_batch().await? {
+                    Some(batch) => batch,
+                    None => break,
+                };
+                let tx = tx.clone();
+                let parser = parser.clone();
+                let stop = stop.clone();
+                let mapper = mapper.clone();
+                // spawn a mapper task per batch
+                handles.push(tokio::spawn(async move {
+                    let mapped = mapper.map(batch, parser.as_ref());
+                    if mapped.stopped {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                    tx.send(mapped).await
+                }));
+            }
+            // await all tasks (don't worry, they're not lazy)
+            // propagate any errors up the chain
+            for handle in handles {
+                handle.await??;
+            }
+            Ok(reader)
+        });
+
+    // reduce here until there are no more senders. If the reducer fails,
+    // the reader only fails because nobody's listening.
+    while let Some(batch) = rx.recv().await {
+        reducer.reduce(batch)?;
+    }
+    let reader = reader_handle.await??;
+    Ok((reader, reducer.stopped_at.is_some()))
+}
+
+/// Identifies a file regardless of its name, so we can tell when a path
+/// has been pointed at a new file, e.g. by logrotate
+#[cfg(unix)]
+fn file_id(meta: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+/// There's no inode to go on, so rotation can't be detected
+#[cfg(not(unix))]
+fn file_id(_: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Combines mapped batches as they arrive, in any order
+struct Reducer {
+    summary: Summary<AsyncBatchReaderParserError>,
+    /// Batches are held on to until every earlier one has been combined,
+    /// so we know how many lines came before them and whether an earlier
+    /// line told us to stop. Keyed by sequence number.
+    pending: BTreeMap<usize, MappedBatch<AsyncBatchReaderParserError>>,
+    next_sequence: usize,
+    first_line: usize,
+    /// The first batch a line told us to stop reading in. Anything after
+    /// it is dropped, however much was read before the reader noticed.
+    stopped_at: Option<usize>,
+    /// Rejected lines are written as their batch's errors are numbered,
+    /// so in order
+    rejects: Option<Arc<RejectFile>>,
+    source: String,
+    delim: u8,
+}
+
+impl Reducer {
+    /// Starting from `summary`, so even empty input keeps its settings
+    fn new(
+        summary: Summary<AsyncBatchReaderParserError>,
+        rejects: Option<Arc<RejectFile>>,
+        source: &str,
+        delim: u8,
+    ) -> Self {
+        Self {
+            summary,
+            pending: BTreeMap::new(),
+            next_sequence: 0,
+            first_line: 0,
+            stopped_at: None,
+            rejects,
+            source: source.to_owned(),
+            delim,
+        }
+    }
+
+    fn reduce(&mut self, batch: MappedBatch<AsyncBatchReaderParserError>) -> std::io::Result<()> {
+        if matches!(self.stopped_at, Some(stopped_at) if batch.sequence > stopped_at) {
+            return Ok(());
+        }
+        if batch.stopped {
+            self.stopped_at = Some(batch.sequence);
+            self.pending.split_off(&(batch.sequence + 1));
+        }
+        self.pending.insert(batch.sequence, batch);
+        while let Some(mut batch) = self.pending.remove(&self.next_sequence) {
+            let mut errors = std::mem::take(batch.summary.errors_mut());
+            errors.shift(self.first_line);
+            self.summary.combine(batch.summary);
+            self.summary.errors_mut().combine(errors);
+            if let Some(rejects) = &self.rejects {
+                for rejected in batch.rejected.iter_mut() {
+                    rejected.line_number += self.first_line;
+                }
+                rejects.write(&self.source, &batch.rejected, self.delim)?;
+            }
+            self.first_line += batch.lines;
+            self.next_sequence += 1;
+        }
+        Ok(())
+    }
 }
 
 impl Default for AsyncBufReaderSummarizer {
@@ -33,99 +271,442 @@ impl Default for AsyncBufReaderSummarizer {
             reducer_channel_size: DEFAULT_REDUCER_CHANNEL_SIZE,
             batch_size: DEFAULT_BATCH_SIZE,
             delim: DEFAULT_DELIMITER,
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            options: SummaryOptions::default(),
         }
     }
 }
 
 impl AsyncBufReaderSummarizer {
-    pub fn new(reducer_channel_size: usize, batch_size: usize, delim: u8) -> Self {
+    pub fn new(
+        reducer_channel_size: usize,
+        batch_size: usize,
+        delim: u8,
+        max_line_length: usize,
+    ) -> Self {
         Self {
             reducer_channel_size,
             batch_size,
             delim,
+            max_line_length,
+            options: SummaryOptions::default(),
         }
     }
+
+    /// Keep everything `options` asks for
+    pub fn with_options(mut self, options: SummaryOptions) -> Self {
+        self.options = options;
+        self
+    }
 }
 
 impl Summarizer for AsyncBufReaderSummarizer {
     type ParserError = AsyncBatchReaderParserError;
     type SummarizerError = AsyncBufReaderSummarizerError;
 
-    /// Rather complex map reduce algorithm to read a file in batches
-    /// and delegate each batch to a new tokio task (mapper) before
-    /// sending to a single reducer task.
     fn summarize<P: Parser>(
         &self,
         logfile: &str,
+        parser: P,
     ) -> Result<Summary<Self::ParserError>, Self::SummarizerError> {
-        #[cfg(feature = "console")]
-        console_subscriber::init();
-
-        // Copy out these values to avoid lifetime shenanigans
-        let logfile = logfile.to_owned();
-        let delim = self.delim;
-        let batch_size = self.batch_size;
-        let reducer_channel_size = self.reducer_channel_size;
-        // start a tokio runtime here just so it's not a hard requirement
-        // for the application
-        let rt = Runtime::new()?;
-
-        let res: Result<Summary<Self::ParserError>, AsyncBufReaderSummarizerError> =
-            rt.block_on(async {
-                // channels from mappers to reducers
-                let (tx, mut rx) =
-                    tokio::sync::mpsc::channel::<Summary<Self::ParserError>>(reducer_channel_size);
-
-                // the reader task which spawns the mappers
-                let reader_handle: JoinHandle<Result<(), AsyncBufReaderSummarizerError>> =
-                    tokio::spawn(async move {
-                        let file = File::open(logfile).await?;
-                        let meta = file.metadata().await?;
-                        let mut reader = AsyncBatchReader::new(file, batch_size, delim);
-                        // Preallocate this based on the file size vs batch size. Add one to account for truncating
-                        let mut handles =
-                            Vec::with_capacity(((meta.len() / batch_size as u64) + 1) as usize);
-                        while let Some(data) = reader.read_batch().await? {
-                            let tx = tx.clone();
-                            // spawn a mapper task per batch
-                            handles.push(tokio::spawn(async move {
-                                let mut summary = Summary::new();
-                                for line in data.byte_lines(delim) {
-                                    if let Ok(meta) = P::parse(line) {
-                                        summary.accumulate(&meta);
-                                    } else {
-                                        summary.register_error(1);
-                                    }
-                                }
-                                tx.send(summary).await
-                            }));
-                        }
-                        // await all tasks (don't worry, they're not lazy)
-                        // propagate any errors up the chain
-                        for handle in handles {
-                            handle.await??;
+        let rt = runtime()?;
+        rt.block_on(self.summarize_path(logfile.to_owned(), Arc::new(parser)))
+    }
+
+    /// Every file gets its own reader and reducer task, so they're all
+    /// read concurrently on the same runtime
+    fn summarize_files<P: Parser>(
+        &self,
+        logfiles: &[String],
+        parser: P,
+    ) -> Result<Vec<Summary<Self::ParserError>>, Self::SummarizerError> {
+        let rt = runtime()?;
+        let parser = Arc::new(parser);
+        rt.block_on(async {
+            let handles: Vec<JoinHandle<_>> = logfiles
+                .iter()
+                .map(|logfile| {
+                    let summarizer = self.clone();
+                    let logfile = logfile.clone();
+                    let parser = parser.clone();
+                    tokio::spawn(async move { summarizer.summarize_path(logfile, parser).await })
+                })
+                .collect();
+            let mut summaries = Vec::with_capacity(handles.len());
+            for handle in handles {
+                summaries.push(handle.await??);
+            }
+            Ok(summaries)
+        })
+    }
+}
+
+/// Start a tokio runtime per call just so it's not a hard requirement for
+/// the application
+fn runtime() -> Result<Runtime, AsyncBufReaderSummarizerError> {
+    #[cfg(feature = "console")]
+    console_subscriber::init();
+
+    Ok(Runtime::new()?)
+}
+
+impl AsyncBufReaderSummarizer {
+    /// Summarize anything readable, e.g. stdin or a socket, rather than
+    /// a named file. Compressed input is decompressed as with files.
+    pub fn summarize_reader<R, P>(
+        &self,
+        reader: R,
+        parser: P,
+    ) -> Result<Summary<AsyncBatchReaderParserError>, AsyncBufReaderSummarizerError>
+    where
+        R: AsyncRead + Unpin + Send + Sync + 'static,
+        P: Parser,
+    {
+        let rt = runtime()?;
+        rt.block_on(self.summarize_async(reader, Arc::new(parser), STDIN_PATH.to_owned()))
+    }
+
+    /// Summarize a file which is still being written to, like `tail -f`.
+    /// Everything in the file is read, then whenever `interval` has passed
+    /// any appended lines are read too. `on_update` is called with the
+    /// summary so far after each read, and following stops once it returns
+    /// false. A partial line at the end of the file is held back until it's
+    /// finished. If the file is truncated it's read again from the start,
+    /// adding to the summary. If it's rotated, i.e. `logfile` now names a
+    /// different file, the rest of the old file is read before switching
+    /// to the new one.
+    pub fn follow<P, F>(
+        &self,
+        logfile: &str,
+        parser: P,
+        interval: Duration,
+        mut on_update: F,
+    ) -> Result<(), AsyncBufReaderSummarizerError>
+    where
+        P: Parser,
+        F: FnMut(&Summary<AsyncBatchReaderParserError>) -> bool,
+    {
+        let mapper = self.mapper(logfile);
+        let parser = Arc::new(parser);
+        let rt = runtime()?;
+        rt.block_on(async {
+            let file = File::open(logfile).await?;
+            let mut id = file_id(&file.metadata().await?);
+            let mut reader =
+                AsyncBatchReader::new(file, self.batch_size, self.delim, self.max_line_length)
+                    .following();
+            let mut reducer = Reducer::new(
+                mapper.options.new_summary(),
+                self.options.rejects().cloned(),
+                logfile,
+                self.delim,
+            );
+            loop {
+                let (read, stopped) = map_reduce(
+                    reader,
+                    &parser,
+                    &mut reducer,
+                    &mapper,
+                    self.reducer_channel_size,
+                )
+                .await?;
+                reader = read;
+                if !on_update(&reducer.summary) || stopped {
+                    return Ok(());
+                }
+                tokio::time::sleep(interval).await;
+                let rotated = match tokio::fs::metadata(logfile).await {
+                    Ok(meta) => id.is_some() && file_id(&meta) != id,
+                    // the file has been moved and the new one isn't there yet
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => false,
+                    Err(err) => return Err(err.into()),
+                };
+                if rotated {
+                    // finish off the old file, which may have had more
+                    // written to it before it was moved, then switch over
+                    let file = File::open(logfile).await?;
+                    let (read, _) = map_reduce(
+                        reader,
+                        &parser,
+                        &mut reducer,
+                        &mapper,
+                        self.reducer_channel_size,
+                    )
+                    .await?;
+                    reader = read;
+                    if let Some(batch) = reader.finish() {
+                        reducer.reduce(mapper.map(batch, parser.as_ref()))?;
+                    }
+                    id = file_id(&file.metadata().await?);
+                    *reader.get_mut() = file;
+                    reader.reset();
+                } else if reader.get_mut().metadata().await?.len() < reader.position() {
+                    reader.get_mut().seek(SeekFrom::Start(0)).await?;
+                    reader.reset();
+                }
+            }
+        })
+    }
+
+    async fn summarize_path<P: Parser>(
+        &self,
+        logfile: String,
+        parser: Arc<P>,
+    ) -> Result<Summary<AsyncBatchReaderParserError>, AsyncBufReaderSummarizerError> {
+        if logfile == STDIN_PATH {
+            return self
+                .summarize_async(tokio::io::stdin(), parser, logfile)
+                .await;
+        }
+        let file = File::open(&logfile).await?;
+        self.summarize_async(file, parser, logfile).await
+    }
+
+    /// Summarize `reader` with [map_reduce]. `source` names the input,
+    /// e.g. in the reject file.
+    async fn summarize_async<R, P>(
+        &self,
+        reader: R,
+        parser: Arc<P>,
+        source: String,
+    ) -> Result<Summary<AsyncBatchReaderParserError>, AsyncBufReaderSummarizerError>
+    where
+        R: AsyncRead + Unpin + Send + Sync + 'static,
+        P: Parser,
+    {
+        let reader = decompress_async(reader).await?;
+        let reader =
+            AsyncBatchReader::new(reader, self.batch_size, self.delim, self.max_line_length);
+        let mapper = self.mapper(&source);
+        let mut reducer = Reducer::new(
+            mapper.options.new_summary(),
+            self.options.rejects().cloned(),
+            &source,
+            self.delim,
+        );
+        map_reduce(
+            reader,
+            &parser,
+            &mut reducer,
+            &mapper,
+            self.reducer_channel_size,
+        )
+        .await?;
+        Ok(reducer.summary)
+    }
+
+    /// How to map the batches of `source`
+    fn mapper(&self, source: &str) -> Mapper {
+        Mapper {
+            delim: self.delim,
+            max_line_length: self.max_line_length,
+            // each input makes its own random choices
+            options: self.options.for_part(source.as_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PrintOptions, StringParser};
+
+    #[test]
+    fn test_summarize_reader() {
+        let input = &b"{\"type\":\"a\"}\nnope\n{\"type\":\"b\"}\n{\"type\":\"a\"}"[..];
+        let summary = AsyncBufReaderSummarizer::new(4, 16, DEFAULT_DELIMITER, 64)
+            .summarize_reader(input, StringParser::new("type"))
+            .unwrap();
+        let json = summary.to_json_summary(&PrintOptions::default());
+        assert_eq!(json.total_size, 38);
+        assert_eq!(json.total_errors, 1);
+    }
+
+    #[test]
+    fn test_stops_like_sync() {
+        use crate::{BufReaderSummarizer, TimeWindow, TimestampParser};
+        let input: String = (1..=500)
+            .map(|ts| format!("{{\"type\":\"a\",\"ts\":{}}}\n", ts))
+            .collect();
+        let parser = || {
+            TimestampParser::<StringParser>::new("type").within(TimeWindow::new(
+                None,
+                Some(100),
+                true,
+            ))
+        };
+        let sync = BufReaderSummarizer::default()
+            .summarize_reader(input.as_bytes(), parser())
+            .unwrap()
+            .to_json_summary(&PrintOptions::default());
+        // small batches, so plenty are read past the end of the window
+        // before the reader hears about it
+        for _ in 0..10 {
+            let summary = AsyncBufReaderSummarizer::new(4, 64, DEFAULT_DELIMITER, 1024)
+                .summarize_reader(std::io::Cursor::new(input.clone()), parser())
+                .unwrap()
+                .to_json_summary(&PrintOptions::default());
+            assert_eq!(
+                (
+                    summary.total_size,
+                    summary.filtered_lines,
+                    summary.filtered_size
+                ),
+                (sync.total_size, sync.filtered_lines, sync.filtered_size)
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_file() {
+        use crate::{TopBy, TopK};
+        let summary = AsyncBufReaderSummarizer::default()
+            .with_options(
+                SummaryOptions::new()
+                    .with_top_k(Some(TopK::new(2, TopBy::Bytes)))
+                    .with_buckets(true),
+            )
+            .summarize_reader(&b""[..], StringParser::new("type"))
+            .unwrap();
+        // still bounded, so it merges like any other
+        let json = serde_json::to_value(&summary).unwrap();
+        assert!(!json["top"].is_null());
+        assert!(!json["buckets"].is_null());
+    }
+
+    #[test]
+    fn test_reject_file() {
+        let path = std::env::temp_dir().join(format!("log_parser_reject_{}", std::process::id()));
+        let rejects = Arc::new(RejectFile::create(&path, true).unwrap());
+        // small batches, so rejected lines arrive from several mappers
+        let input: Vec<u8> = (0..50)
+            .map(|i| match i % 3 {
+                0 => format!("bad {}\n", i),
+                _ => "{\"type\":\"a\"}\n".to_owned(),
+            })
+            .collect::<String>()
+            .into_bytes();
+        AsyncBufReaderSummarizer::new(4, 32, DEFAULT_DELIMITER, 64)
+            .with_options(SummaryOptions::new().with_rejects(Some(rejects)))
+            .summarize_reader(std::io::Cursor::new(input), StringParser::new("type"))
+            .unwrap();
+        let expected: String = (0..50)
+            .step_by(3)
+            .map(|i| {
+                format!(
+                    "-:{}: no string value for the group key\tbad {}\n",
+                    i + 1,
+                    i
+                )
+            })
+            .collect();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), expected);
+        // a line too long to keep still leaves a trace
+        let rejects = Arc::new(RejectFile::create(&path, true).unwrap());
+        let input = format!("{{\"type\":\"a\"}}\n{}\n", "x".repeat(100));
+        AsyncBufReaderSummarizer::new(4, 32, DEFAULT_DELIMITER, 64)
+            .with_options(SummaryOptions::new().with_rejects(Some(rejects)))
+            .summarize_reader(std::io::Cursor::new(input), StringParser::new("type"))
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "-:2: line of 101 bytes was skipped\t\n"
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_summarize_files() {
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let files: Vec<String> = [("a", 2), ("b", 3)]
+            .iter()
+            .map(|(name, lines)| {
+                let path = dir.join(format!("log_parser_files_{}_{}.log", pid, name));
+                std::fs::write(&path, format!("{{\"type\":\"{}\"}}\n", name).repeat(*lines))
+                    .unwrap();
+                path.to_string_lossy().into_owned()
+            })
+            .collect();
+        let summaries = AsyncBufReaderSummarizer::default()
+            .summarize_files(&files, StringParser::new("type"))
+            .unwrap();
+        let sizes: Vec<_> = summaries
+            .iter()
+            .map(|summary| summary.to_json_summary(&PrintOptions::default()).total_size)
+            .collect();
+        assert_eq!(sizes, vec![26, 39]);
+        for file in files {
+            std::fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_follow() {
+        use std::io::Write;
+        let path = std::env::temp_dir().join(format!("log_parser_tail_{}.log", std::process::id()));
+        std::fs::write(&path, "{\"type\":\"a\"}\n").unwrap();
+        let mut sizes = vec![];
+        AsyncBufReaderSummarizer::default()
+            .follow(
+                &path.to_string_lossy(),
+                StringParser::new("type"),
+                Duration::from_millis(10),
+                |summary| {
+                    sizes.push(summary.to_json_summary(&PrintOptions::default()).total_size);
+                    match sizes.len() {
+                        1 => {
+                            let mut file = std::fs::OpenOptions::new()
+                                .append(true)
+                                .open(&path)
+                                .unwrap();
+                            file.write_all(b"{\"type\":\"b\"}\n{\"type\":\"c\"")
+                                .unwrap();
                         }
-                        Ok(())
-                    });
-
-                // the reducer task which listens to rx until there are no
-                // more senders
-                let reducer_handle = tokio::spawn(async move {
-                    let mut summary = Summary::default();
-                    while let Some(metadata) = rx.recv().await {
-                        summary.combine(metadata)
+                        // truncated and rewritten
+                        2 => std::fs::write(&path, "{\"type\":\"d\"}\n").unwrap(),
+                        _ => {}
+                    }
+                    sizes.len() < 3
+                },
+            )
+            .unwrap();
+        assert_eq!(sizes, vec![13, 26, 39]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_rotation() {
+        use std::io::Write;
+        let path =
+            std::env::temp_dir().join(format!("log_parser_rotate_{}.log", std::process::id()));
+        let rotated = path.with_extension("log.1");
+        std::fs::write(&path, "{\"type\":\"a\"}\n{\"type\":\"b\"").unwrap();
+        let mut sizes = vec![];
+        AsyncBufReaderSummarizer::default()
+            .follow(
+                &path.to_string_lossy(),
+                StringParser::new("type"),
+                Duration::from_millis(10),
+                |summary| {
+                    sizes.push(summary.to_json_summary(&PrintOptions::default()).total_size);
+                    if sizes.len() == 1 {
+                        std::fs::rename(&path, &rotated).unwrap();
+                        // still being written to after it's been moved
+                        let mut old = std::fs::OpenOptions::new()
+                            .append(true)
+                            .open(&rotated)
+                            .unwrap();
+                        old.write_all(b"}\n{\"type\":\"c\"}").unwrap();
+                        std::fs::write(&path, "{\"type\":\"d\"}\n").unwrap();
                     }
-                    summary
-                });
-
-                // join it all together!
-                let (summary_result, reader_result) = tokio::join![reducer_handle, reader_handle];
-
-                // guh this nested error handling is ugly
-                reader_result??;
-                let summary = summary_result?;
-                Ok(summary)
-            });
-        res
+                    sizes.len() < 2
+                },
+            )
+            .unwrap();
+        assert_eq!(sizes, vec![13, 51]);
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(rotated).unwrap();
     }
 }
@@ -1,32 +1,67 @@
 use crate::ErrorSummary;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AsyncBatchReaderParserError {
-    /// Just the total number of errors. We can't reasonably figure out the
-    /// line number when batch processing.
-    errors: usize,
+    /// The lines we failed to parse. These are relative to the start of the
+    /// batch until the reducer shifts them. They count from 0, but are
+    /// shown counting from 1.
+    error_lines: Vec<usize>,
 }
 
 impl ErrorSummary for AsyncBatchReaderParserError {
     fn display_error(&self) {
-        if self.errors > 0 {
-            self.red_stderr_line(format!(
-                "{} line{} could not be parsed\n",
-                self.errors,
-                if self.errors == 1 { "" } else { "s" }
-            ));
+        if !self.error_lines.is_empty() {
+            if self.error_lines.len() == 1 {
+                self.red_stderr_line(format!("Failed to parse line {}", self.error_lines[0] + 1));
+            } else {
+                self.red_stderr_line("Failed to parse the following lines:");
+                for line in self.error_lines.iter().sorted() {
+                    self.red_stderr_line(format!("{}", line + 1));
+                }
+            }
+            self.red_stderr_line("");
         }
     }
 
     fn accumulate(&mut self, error: usize) {
-        self.errors += error;
+        self.error_lines.push(error)
     }
 
     fn combine(&mut self, other: Self) {
-        self.errors += other.errors;
+        self.error_lines.extend(other.error_lines.iter());
     }
 
     fn total_errors(&self) -> usize {
-        self.errors
+        self.error_lines.len()
+    }
+
+    fn error_lines(&self) -> Option<&[usize]> {
+        Some(self.error_lines.as_slice())
+    }
+
+    /// Move the errors of a batch to where the batch starts in the file
+    fn shift(&mut self, first_line: usize) {
+        for line in self.error_lines.iter_mut() {
+            *line += first_line;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shift() {
+        let mut first = AsyncBatchReaderParserError::default();
+        first.accumulate(2);
+        let mut second = AsyncBatchReaderParserError::default();
+        second.accumulate(0);
+        second.accumulate(3);
+        second.shift(10);
+        first.combine(second);
+        assert_eq!(first.error_lines, vec![2, 10, 13]);
     }
 }
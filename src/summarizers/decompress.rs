@@ -0,0 +1,238 @@
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use tokio::io::{AsyncBufReadExt, AsyncRead};
+
+/// A compression format recognised by its magic bytes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+/// Enough bytes to recognise any of the formats above
+const MAGIC_LEN: usize = 6;
+
+impl Compression {
+    /// Sniff the format from the first few bytes of a file. Anything we
+    /// don't recognise is treated as plain text.
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else if header.starts_with(b"BZh") {
+            Some(Compression::Bzip2)
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(Compression::Xz)
+        } else {
+            None
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Bzip2 => "bzip2",
+            Compression::Xz => "xz",
+        }
+    }
+
+    /// The cargo feature needed to read this format
+    fn feature(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+            Compression::Bzip2 => "bz2",
+            Compression::Xz => "xz",
+        }
+    }
+
+    /// The error for a compressed file which can't be read as-is
+    pub fn unsupported(&self, reason: &str) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} compressed input {}", self.name(), reason),
+        )
+    }
+
+    fn disabled(&self) -> io::Error {
+        self.unsupported(&format!(
+            "needs the `{}` feature to be enabled",
+            self.feature()
+        ))
+    }
+}
+
+/// Read the start of `reader` without consuming it
+fn peek_magic<R: BufRead>(reader: &mut R) -> io::Result<Option<Compression>> {
+    let header = reader.fill_buf()?;
+    Ok(Compression::detect(&header[..header.len().min(MAGIC_LEN)]))
+}
+
+/// Wrap `reader` in a decoder if it's compressed. Concatenated gzip
+/// members and zstd frames are all read.
+pub fn decompress<'a, R: Read + 'a>(reader: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    let mut reader = BufReader::new(reader);
+    let compression = match peek_magic(&mut reader)? {
+        Some(compression) => compression,
+        None => return Ok(Box::new(reader)),
+    };
+    match compression {
+        #[cfg(feature = "gz")]
+        Compression::Gzip => Ok(Box::new(BufReader::new(
+            flate2::bufread::MultiGzDecoder::new(reader),
+        ))),
+        #[cfg(feature = "zst")]
+        Compression::Zstd => Ok(Box::new(BufReader::new(
+            zstd::stream::read::Decoder::with_buffer(reader)?,
+        ))),
+        #[cfg(feature = "bz2")]
+        Compression::Bzip2 => Ok(Box::new(BufReader::new(
+            bzip2::bufread::MultiBzDecoder::new(reader),
+        ))),
+        #[cfg(feature = "xz")]
+        Compression::Xz => Ok(Box::new(BufReader::new(
+            xz2::bufread::XzDecoder::new_multi_decoder(reader),
+        ))),
+        #[allow(unreachable_patterns)]
+        compression => Err(compression.disabled()),
+    }
+}
+
+pub type BoxAsyncRead = Box<dyn AsyncRead + Unpin + Send + Sync>;
+
+/// The async equivalent of [decompress]
+pub async fn decompress_async<R: AsyncRead + Unpin + Send + Sync + 'static>(
+    reader: R,
+) -> io::Result<BoxAsyncRead> {
+    let mut reader = tokio::io::BufReader::new(reader);
+    let header = reader.fill_buf().await?;
+    let compression = match Compression::detect(&header[..header.len().min(MAGIC_LEN)]) {
+        Some(compression) => compression,
+        None => return Ok(Box::new(reader)),
+    };
+    match compression {
+        #[cfg(feature = "gz")]
+        Compression::Gzip => {
+            let mut decoder = async_compression::tokio::bufread::GzipDecoder::new(reader);
+            decoder.multiple_members(true);
+            Ok(Box::new(decoder))
+        }
+        #[cfg(feature = "zst")]
+        Compression::Zstd => {
+            let mut decoder = async_compression::tokio::bufread::ZstdDecoder::new(reader);
+            decoder.multiple_members(true);
+            Ok(Box::new(decoder))
+        }
+        #[cfg(feature = "bz2")]
+        Compression::Bzip2 => {
+            let mut decoder = async_compression::tokio::bufread::BzDecoder::new(reader);
+            decoder.multiple_members(true);
+            Ok(Box::new(decoder))
+        }
+        #[cfg(feature = "xz")]
+        Compression::Xz => {
+            let mut decoder = async_compression::tokio::bufread::XzDecoder::new(reader);
+            decoder.multiple_members(true);
+            Ok(Box::new(decoder))
+        }
+        #[allow(unreachable_patterns)]
+        compression => Err(compression.disabled()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Compression::detect(b"{\"type\":1}"), None);
+        assert_eq!(Compression::detect(b""), None);
+        assert_eq!(
+            Compression::detect(&[0x1f, 0x8b, 0x08, 0x00]),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd, 0x00]),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::detect(b"BZh91AY"), Some(Compression::Bzip2));
+        assert_eq!(
+            Compression::detect(&[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00]),
+            Some(Compression::Xz)
+        );
+    }
+
+    #[test]
+    fn test_plain_text_passes_through() {
+        let mut out = String::new();
+        decompress(&b"a\nb\n"[..])
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "a\nb\n");
+    }
+
+    #[cfg(feature = "gz")]
+    fn gzip_members(members: &[&str]) -> Vec<u8> {
+        use std::io::Write;
+        let mut out = Vec::new();
+        for member in members {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+            encoder.write_all(member.as_bytes()).unwrap();
+            out.extend(encoder.finish().unwrap());
+        }
+        out
+    }
+
+    #[cfg(feature = "gz")]
+    #[test]
+    fn test_multi_member_gzip() {
+        let data = gzip_members(&["a\n", "b\n"]);
+        let mut out = String::new();
+        decompress(std::io::Cursor::new(data))
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "a\nb\n");
+    }
+
+    #[cfg(feature = "gz")]
+    #[tokio::test]
+    async fn test_multi_member_gzip_async() {
+        use tokio::io::AsyncReadExt;
+        let data = gzip_members(&["a\n", "b\n"]);
+        let mut out = String::new();
+        decompress_async(std::io::Cursor::new(data))
+            .await
+            .unwrap()
+            .read_to_string(&mut out)
+            .await
+            .unwrap();
+        assert_eq!(out, "a\nb\n");
+    }
+
+    #[cfg(feature = "zst")]
+    #[test]
+    fn test_zstd_frames() {
+        let mut data = zstd::encode_all(&b"a\n"[..], 0).unwrap();
+        data.extend(zstd::encode_all(&b"b\n"[..], 0).unwrap());
+        let mut out = String::new();
+        decompress(std::io::Cursor::new(data))
+            .unwrap()
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "a\nb\n");
+    }
+
+    #[cfg(not(feature = "gz"))]
+    #[test]
+    fn test_disabled_codec_is_an_error() {
+        let err = decompress(&[0x1f, 0x8b, 0x08, 0x00][..]).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
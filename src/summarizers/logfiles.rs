@@ -0,0 +1,94 @@
+use crate::STDIN_PATH;
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Expand the paths given on the command line in to a list of files. Glob
+/// patterns are matched here rather than relying on the shell, and
+/// directories are walked recursively. Anything else is passed through
+/// as-is, so a missing file is reported when it's opened. Files are only
+/// listed once, in the order they were first found.
+pub fn expand_logfiles<S: AsRef<str>>(patterns: &[S]) -> io::Result<Vec<String>> {
+    let mut seen = HashSet::new();
+    let mut logfiles = Vec::new();
+    for pattern in patterns.iter().map(AsRef::as_ref) {
+        let paths = if pattern == STDIN_PATH {
+            vec![pattern.to_owned()]
+        } else if pattern.contains(['*', '?', '[']) {
+            let paths = glob_files(pattern)?;
+            if paths.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no files match {}", pattern),
+                ));
+            }
+            paths
+        } else if Path::new(pattern).is_dir() {
+            walk_dir(Path::new(pattern))?
+        } else {
+            vec![pattern.to_owned()]
+        };
+        for path in paths {
+            if seen.insert(path.clone()) {
+                logfiles.push(path);
+            }
+        }
+    }
+    Ok(logfiles)
+}
+
+fn glob_files(pattern: &str) -> io::Result<Vec<String>> {
+    let paths = glob::glob(pattern)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.map_err(|err| io::Error::new(err.error().kind(), err.to_string()))?;
+        if path.is_dir() {
+            files.extend(walk_dir(&path)?);
+        } else {
+            files.push(path.to_string_lossy().into_owned());
+        }
+    }
+    Ok(files)
+}
+
+/// Every file beneath `dir`, sorted so runs are repeatable
+fn walk_dir(dir: &Path) -> io::Result<Vec<String>> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            files.push(entry.path().to_string_lossy().into_owned());
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_expand_logfiles() {
+        let dir = std::env::temp_dir().join(format!("log_parser_expand_{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        for file in ["a.log", "b.log.1", "c.txt", "nested/d.log"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let root = dir.to_string_lossy();
+        let pattern = format!("{}/*.log*", root);
+        let logfiles = expand_logfiles(&[pattern.as_str(), root.as_ref(), "-"]).unwrap();
+        let names: Vec<_> = logfiles
+            .iter()
+            .map(|f| f.strip_prefix(root.as_ref()).unwrap_or(f))
+            .collect();
+        assert_eq!(
+            names,
+            vec!["/a.log", "/b.log.1", "/c.txt", "/nested/d.log", "-"]
+        );
+        assert!(expand_logfiles(&[format!("{}/*.gz", root)]).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,17 +1,34 @@
 mod async_buf_reader;
+/// A utility for iterating byte arrays over delimiters.
+mod byte_lines;
+/// Sniffing and unwrapping compressed input.
+mod decompress;
+/// Finding the files to summarize.
+mod logfiles;
+/// What summaries keep, shared by every summarizer.
+mod options;
+mod rayon_mmap;
+/// Quarantining lines which fail to parse.
+mod rejects;
 mod sync_buf_reader;
 
 use crate::Parser;
 pub use async_buf_reader::{
     AsyncBufReaderSummarizer, AsyncBufReaderSummarizerError, DEFAULT_BATCH_SIZE,
-    DEFAULT_REDUCER_CHANNEL_SIZE,
+    DEFAULT_FOLLOW_INTERVAL_MS, DEFAULT_MAX_LINE_LENGTH, DEFAULT_REDUCER_CHANNEL_SIZE,
 };
 
 use crate::summary::{ErrorSummary, Summary};
 
+pub use logfiles::expand_logfiles;
+pub use options::SummaryOptions;
+pub use rayon_mmap::{RayonMmapSummarizer, DEFAULT_CHUNK_SIZE};
+pub use rejects::RejectFile;
 pub use sync_buf_reader::{BufReaderSummarizer, DEFAULT_BUFFER_CAPACITY};
 
 pub const DEFAULT_DELIMITER: u8 = b'\n';
+/// Passing this as the logfile reads from stdin instead
+pub const STDIN_PATH: &str = "-";
 
 /// The worker function for the process. Takes a Parser and aggregates
 /// a [Summary] over the entire file
@@ -22,9 +39,21 @@ pub trait Summarizer {
     /// found)
     type SummarizerError;
 
-    /// Take a file, read and parse it, and return a [Summary]
+    /// Take a file, read and parse each line with `parser`, and return a
+    /// [Summary]. A logfile of [STDIN_PATH] reads from stdin, where the
+    /// summarizer supports it.
     fn summarize<P: Parser>(
         &self,
         logfile: &str,
+        parser: P,
     ) -> Result<Summary<Self::ParserError>, Self::SummarizerError>;
+
+    /// Summarize each of `logfiles` with the same `parser`, returning one
+    /// [Summary] per file in the same order. Combine them with
+    /// [Summary::combine] for a total.
+    fn summarize_files<P: Parser>(
+        &self,
+        logfiles: &[String],
+        parser: P,
+    ) -> Result<Vec<Summary<Self::ParserError>>, Self::SummarizerError>;
 }
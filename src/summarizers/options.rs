@@ -0,0 +1,72 @@
+use crate::summarizers::rejects::RejectFile;
+use crate::{ErrorSummary, Sampling, Summary, TopK};
+use std::sync::Arc;
+
+/// What a summarizer keeps besides each group's sizes, and where it
+/// writes lines which fail to parse. Every summary a summarizer starts,
+/// whether for a file, a batch or a chunk, is built from these.
+#[derive(Clone, Default)]
+pub struct SummaryOptions {
+    /// Only keep the heaviest groups
+    top_k: Option<TopK>,
+    /// Count lines per minute as well
+    bucketing: bool,
+    /// Keep example lines from each group
+    sampling: Option<Sampling>,
+    /// Where to write lines which fail to parse
+    rejects: Option<Arc<RejectFile>>,
+}
+
+impl SummaryOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep memory bounded by only tracking the heaviest groups, if
+    /// `top_k` is given
+    pub fn with_top_k(mut self, top_k: Option<TopK>) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    /// Count lines in each minute, so they can be printed in time buckets
+    pub fn with_buckets(mut self, bucketing: bool) -> Self {
+        self.bucketing = bucketing;
+        self
+    }
+
+    /// Keep a few example lines from each group, if `sampling` is given
+    pub fn with_samples(mut self, sampling: Option<Sampling>) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Write lines which fail to parse to `rejects`, if given, in order.
+    /// Lines too long for the async summarizer to hold in memory are
+    /// written as an empty line, so they can still be found with
+    /// `--annotate-rejects`.
+    pub fn with_rejects(mut self, rejects: Option<Arc<RejectFile>>) -> Self {
+        self.rejects = rejects;
+        self
+    }
+
+    pub(crate) fn rejects(&self) -> Option<&Arc<RejectFile>> {
+        self.rejects.as_ref()
+    }
+
+    /// The same options for a part of the input summarized separately, e.g.
+    /// a file or a batch, which makes its own random choices
+    pub(crate) fn for_part(&self, part: &[u8]) -> Self {
+        Self {
+            sampling: self.sampling.map(|sampling| sampling.for_part(part)),
+            ..self.clone()
+        }
+    }
+
+    /// An empty summary to accumulate lines in to
+    pub(crate) fn new_summary<E: ErrorSummary>(&self) -> Summary<E> {
+        Summary::with_top_k(self.top_k)
+            .with_buckets(self.bucketing)
+            .with_samples(self.sampling)
+    }
+}
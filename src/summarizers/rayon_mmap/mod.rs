@@ -0,0 +1,266 @@
+mod parser_error;
+
+use crate::summarizers::byte_lines::ByteLinesExt;
+use crate::summarizers::decompress::Compression;
+use crate::summarizers::options::SummaryOptions;
+use crate::summarizers::rayon_mmap::parser_error::RayonMmapParserError;
+use crate::summarizers::rejects::{Rejected, RejectedLines};
+use crate::{ErrorSummary, LineFilter, Parser, Summarizer, Summary, DEFAULT_DELIMITER, STDIN_PATH};
+use memmap2::Mmap;
+use rayon::prelude::*;
+use std::fs::File;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub const DEFAULT_CHUNK_SIZE: usize = 1_048_576;
+
+pub struct RayonMmapSummarizer {
+    /// The approximate size of each chunk handed to a worker. Chunks are
+    /// extended to the next delimiter.
+    chunk_size: usize,
+    /// The character to split on
+    delim: u8,
+    /// What to keep, and where to write lines which fail to parse
+    options: SummaryOptions,
+}
+
+impl Default for RayonMmapSummarizer {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHUNK_SIZE, DEFAULT_DELIMITER)
+    }
+}
+
+impl RayonMmapSummarizer {
+    pub fn new(chunk_size: usize, delim: u8) -> Self {
+        Self {
+            chunk_size,
+            delim,
+            options: SummaryOptions::default(),
+        }
+    }
+
+    /// Keep everything `options` asks for
+    pub fn with_options(mut self, options: SummaryOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl Summarizer for RayonMmapSummarizer {
+    type ParserError = RayonMmapParserError;
+    type SummarizerError = std::io::Error;
+
+    fn summarize<P: Parser>(
+        &self,
+        logfile: &str,
+        parser: P,
+    ) -> Result<Summary<Self::ParserError>, Self::SummarizerError> {
+        self.summarize_mmap(logfile, &parser)
+    }
+
+    /// Files are mapped in parallel too, sharing the same thread pool as
+    /// their chunks
+    fn summarize_files<P: Parser>(
+        &self,
+        logfiles: &[String],
+        parser: P,
+    ) -> Result<Vec<Summary<Self::ParserError>>, Self::SummarizerError> {
+        logfiles
+            .par_iter()
+            .map(|logfile| self.summarize_mmap(logfile, &parser))
+            .collect()
+    }
+}
+
+impl RayonMmapSummarizer {
+    /// Map the whole file in to memory and let rayon fold each chunk in to
+    /// a [Summary] before combining them. The OS does the reading for us.
+    fn summarize_mmap<P: Parser>(
+        &self,
+        logfile: &str,
+        parser: &P,
+    ) -> std::io::Result<Summary<RayonMmapParserError>> {
+        if logfile == STDIN_PATH {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "stdin can't be memory mapped, use sync or async",
+            ));
+        }
+        // each chunk makes its own random choices
+        let options = self.options.for_part(logfile.as_bytes());
+        let new_summary = |chunk: usize| {
+            options
+                .for_part(&(chunk as u64).to_le_bytes())
+                .new_summary()
+        };
+        let file = File::open(logfile)?;
+        // mapping an empty file is an error on some platforms
+        if file.metadata()?.len() == 0 {
+            return Ok(new_summary(0));
+        }
+        // Safety: the mapping is only valid while nobody truncates the file
+        // underneath us. That's a risk we take for reading logs, the worst
+        // case is a SIGBUS.
+        let mmap = unsafe { Mmap::map(&file)? };
+        // there's nothing to split up until it's been decompressed
+        if let Some(compression) = Compression::detect(&mmap) {
+            return Err(compression.unsupported("can't be memory mapped, use sync or async"));
+        }
+        let delim = self.delim;
+        let rejecting = self.options.rejects().is_some();
+        // the first chunk a line told us to stop reading in, so later ones
+        // needn't bother
+        let stop_at = AtomicUsize::new(usize::MAX);
+        let (summary, rejected, _) = delimited_chunks(&mmap, self.chunk_size, delim)
+            .into_par_iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut summary = new_summary(i);
+                let mut rejected = RejectedLines::default();
+                if i > stop_at.load(Ordering::Relaxed) {
+                    return (summary, rejected, true);
+                }
+                let mut stopped = false;
+                for line in chunk.byte_lines(delim) {
+                    match parser.parse(line) {
+                        Ok(metadata) => {
+                            summary.accumulate(&metadata);
+                            if metadata.filter == LineFilter::Stop {
+                                stop_at.fetch_min(i, Ordering::Relaxed);
+                                stopped = true;
+                                break;
+                            }
+                        }
+                        Err(err) => {
+                            // numbered from the start of the chunk for now
+                            let line_number = rejected.lines;
+                            summary.register_error(line_number);
+                            if rejecting {
+                                rejected
+                                    .rejected
+                                    .push(Rejected::new(line_number, &err, line));
+                            }
+                        }
+                    }
+                    rejected.lines += 1;
+                }
+                (summary, rejected, stopped)
+            })
+            // chunks are reduced in order, so errors and rejected lines can
+            // be numbered from the start of the file, and anything after a
+            // stop can be dropped even if it was already read
+            .reduce(
+                || (Summary::new(), RejectedLines::default(), false),
+                |(mut a, mut a_rejected, a_stopped), (mut b, b_rejected, b_stopped)| {
+                    if !a_stopped {
+                        b.errors_mut().shift(a_rejected.lines);
+                        a.combine(b);
+                        a_rejected.append(b_rejected);
+                    }
+                    (a, a_rejected, a_stopped || b_stopped)
+                },
+            );
+        if let Some(rejects) = self.options.rejects() {
+            rejects.write(logfile, &rejected.rejected, delim)?;
+        }
+        Ok(summary)
+    }
+}
+
+/// Split a buffer in to chunks of at least `chunk_size` bytes which end on
+/// a delimiter (apart from the last). A line longer than `chunk_size` just
+/// makes its chunk longer.
+fn delimited_chunks(buf: &[u8], chunk_size: usize, delim: u8) -> Vec<&[u8]> {
+    let mut chunks = Vec::with_capacity(buf.len() / chunk_size.max(1) + 1);
+    let mut start = 0;
+    while start < buf.len() {
+        let min_end = (start + chunk_size.max(1)).min(buf.len());
+        let end = match buf[min_end - 1..].iter().position(|&b| b == delim) {
+            Some(i) => min_end + i,
+            None => buf.len(),
+        };
+        chunks.push(&buf[start..end]);
+        start = end;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delimited_chunks() {
+        let chunks = delimited_chunks(b"ab\ncd\nefghij\nk", 4, b'\n');
+        assert_eq!(chunks, vec![&b"ab\ncd\n"[..], &b"efghij\n"[..], &b"k"[..]]);
+        let chunks = delimited_chunks(b"ab\ncd\n", 3, b'\n');
+        assert_eq!(chunks, vec![&b"ab\n"[..], &b"cd\n"[..]]);
+        assert!(delimited_chunks(b"", 3, b'\n').is_empty());
+    }
+
+    #[test]
+    fn test_matches_buf_reader() {
+        use crate::{BufReaderSummarizer, PrintOptions, StringParser};
+        let path =
+            std::env::temp_dir().join(format!("log_parser_rayon_{}.log", std::process::id()));
+        // with 16 byte chunks the first boundary falls part way through the
+        // second line, and the last line has no delimiter
+        let input = "{\"type\":\"a\"}\n{\"type\":\"bb\",\"x\":1}\nnope\n{\"type\":\"a\"}\noops\n{\"type\":\"c\"}";
+        std::fs::write(&path, input).unwrap();
+        let logfile = path.to_string_lossy();
+        let options = PrintOptions::default();
+        let rayon = RayonMmapSummarizer::new(16, b'\n')
+            .summarize(&logfile, StringParser::new("type"))
+            .unwrap();
+        let sync = BufReaderSummarizer::default()
+            .summarize(&logfile, StringParser::new("type"))
+            .unwrap();
+        assert_eq!(rayon.errors().error_lines(), Some(&[2, 4][..]));
+        assert_eq!(rayon.errors().error_lines(), sync.errors().error_lines());
+        let rayon = rayon.to_json_summary(&options);
+        let sync = sync.to_json_summary(&options);
+        assert_eq!(rayon.total_size, sync.total_size);
+        assert_eq!(rayon.total_errors, 2);
+        assert_eq!(rayon.total_errors, sync.total_errors);
+        assert_eq!(
+            serde_json::to_value(&rayon.type_size).unwrap(),
+            serde_json::to_value(&sync.type_size).unwrap()
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_empty_file() {
+        use crate::{StringParser, TopBy, TopK};
+        let path =
+            std::env::temp_dir().join(format!("log_parser_rayon_empty_{}.log", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        let summary = RayonMmapSummarizer::default()
+            .with_options(SummaryOptions::new().with_top_k(Some(TopK::new(2, TopBy::Bytes))))
+            .summarize(&path.to_string_lossy(), StringParser::new("type"))
+            .unwrap();
+        // still bounded, so it merges like any other
+        assert!(!serde_json::to_value(&summary).unwrap()["top"].is_null());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_stops_early() {
+        use crate::{PrintOptions, StringParser, TimeWindow, TimestampParser};
+        let path =
+            std::env::temp_dir().join(format!("log_parser_rayon_stop_{}.log", std::process::id()));
+        let line = |ts| format!("{{\"type\":\"a\",\"ts\":{}}}\n", ts);
+        std::fs::write(&path, (1..=9).map(line).collect::<String>()).unwrap();
+        let parser = TimestampParser::<StringParser>::new("type").within(TimeWindow::new(
+            None,
+            Some(4),
+            true,
+        ));
+        // a line per chunk, so later chunks are read before they're dropped
+        let summary = RayonMmapSummarizer::new(1, b'\n')
+            .summarize(&path.to_string_lossy(), parser)
+            .unwrap();
+        let json = summary.to_json_summary(&PrintOptions::default());
+        assert_eq!((json.total_size, json.filtered_lines), (60, 1));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,50 @@
+use crate::ErrorSummary;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RayonMmapParserError {
+    /// The lines we failed to parse. These are relative to the start of the
+    /// chunk until chunks are reduced in order and they're shifted. They
+    /// count from 0, but are shown counting from 1.
+    error_lines: Vec<usize>,
+}
+
+impl ErrorSummary for RayonMmapParserError {
+    fn display_error(&self) {
+        if !self.error_lines.is_empty() {
+            if self.error_lines.len() == 1 {
+                self.red_stderr_line(format!("Failed to parse line {}", self.error_lines[0] + 1));
+            } else {
+                self.red_stderr_line("Failed to parse the following lines:");
+                for line in self.error_lines.iter().sorted() {
+                    self.red_stderr_line(format!("{}", line + 1));
+                }
+            }
+            self.red_stderr_line("");
+        }
+    }
+
+    fn accumulate(&mut self, error: usize) {
+        self.error_lines.push(error)
+    }
+
+    fn combine(&mut self, other: Self) {
+        self.error_lines.extend(other.error_lines.iter());
+    }
+
+    fn total_errors(&self) -> usize {
+        self.error_lines.len()
+    }
+
+    fn error_lines(&self) -> Option<&[usize]> {
+        Some(self.error_lines.as_slice())
+    }
+
+    /// Move the errors of a chunk to where the chunk starts in the file
+    fn shift(&mut self, first_line: usize) {
+        for line in self.error_lines.iter_mut() {
+            *line += first_line;
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// A line which failed to parse, held until it can be written out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejected {
+    /// Relative to wherever the lines it was read with started, until
+    /// it's written
+    pub line_number: usize,
+    pub reason: String,
+    /// Including its delimiter, if it had one
+    pub line: Vec<u8>,
+}
+
+impl Rejected {
+    pub fn new<E: Display>(line_number: usize, error: &E, line: &[u8]) -> Self {
+        Self {
+            line_number,
+            reason: error.to_string(),
+            line: line.to_vec(),
+        }
+    }
+}
+
+/// Lines which failed to parse, along with how many lines were read, so
+/// those from consecutive parts of a file can be put together in order
+#[derive(Debug, Default)]
+pub struct RejectedLines {
+    pub lines: usize,
+    pub rejected: Vec<Rejected>,
+}
+
+impl RejectedLines {
+    /// Add the lines which came straight after these
+    pub fn append(&mut self, other: Self) {
+        let first_line = self.lines;
+        self.rejected
+            .extend(other.rejected.into_iter().map(|mut rejected| {
+                rejected.line_number += first_line;
+                rejected
+            }));
+        self.lines += other.lines;
+    }
+}
+
+/// Where lines which fail to parse are quarantined, verbatim, so they can
+/// be handed back to whoever wrote them. Can be shared between
+/// summarizers and their workers.
+pub struct RejectFile {
+    writer: Mutex<File>,
+    /// Put the file, line number and reason before each line
+    prefixed: bool,
+}
+
+impl RejectFile {
+    /// Truncates `path` if it already exists
+    pub fn create<P: AsRef<Path>>(path: P, prefixed: bool) -> io::Result<Self> {
+        Ok(Self {
+            writer: Mutex::new(File::create(path)?),
+            prefixed,
+        })
+    }
+
+    /// Write some rejected lines from `source`, numbered from the start of
+    /// it. They're written all at once, so lines from workers writing at
+    /// the same time never interleave. Lines missing a delimiter, i.e. the
+    /// last in a file, get one.
+    pub fn write(&self, source: &str, rejected: &[Rejected], delim: u8) -> io::Result<()> {
+        if rejected.is_empty() {
+            return Ok(());
+        }
+        let mut buf = Vec::new();
+        for rejected in rejected {
+            if self.prefixed {
+                // counted from 1, as everywhere they're shown
+                write!(
+                    buf,
+                    "{}:{}: {}\t",
+                    source,
+                    rejected.line_number + 1,
+                    rejected.reason
+                )?;
+            }
+            buf.extend_from_slice(&rejected.line);
+            if rejected.line.last() != Some(&delim) {
+                buf.push(delim);
+            }
+        }
+        // a panic while holding the lock can't have left half a batch
+        // behind, as it's written in one go
+        let mut writer = self
+            .writer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        writer.write_all(&buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_rejects() {
+        let path = std::env::temp_dir().join(format!("log_parser_rejects_{}", std::process::id()));
+        let mut first = RejectedLines {
+            lines: 3,
+            rejected: vec![Rejected::new(1, &"Nope", b"nope\n")],
+        };
+        first.append(RejectedLines {
+            lines: 2,
+            rejected: vec![Rejected::new(1, &"Nope", b"{\"type\"")],
+        });
+        let rejects = RejectFile::create(&path, true).unwrap();
+        rejects.write("app.log", &first.rejected, b'\n').unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "app.log:2: Nope\tnope\napp.log:5: Nope\t{\"type\"\n"
+        );
+        let rejects = RejectFile::create(&path, false).unwrap();
+        rejects.write("app.log", &first.rejected, b'\n').unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "nope\n{\"type\"\n");
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,9 +1,15 @@
 mod parser_error;
 
+use crate::checkpoint::{Checkpoint, FileIdentity, InputCheckpoint};
+use crate::summarizers::decompress::{decompress, Compression};
+use crate::summarizers::options::SummaryOptions;
+use crate::summarizers::rejects::Rejected;
 use crate::summarizers::sync_buf_reader::parser_error::BufReaderParserError;
-use crate::{Parser, Summarizer, Summary, DEFAULT_DELIMITER};
+use crate::{LineFilter, Parser, Summarizer, Summary, DEFAULT_DELIMITER, STDIN_PATH};
+use std::fmt::Display;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 
 pub const DEFAULT_BUFFER_CAPACITY: usize = 1024;
 
@@ -12,34 +18,193 @@ pub struct BufReaderSummarizer {
     capacity: usize,
     /// The character to split on
     delim: u8,
+    /// What to keep, and where to write lines which fail to parse
+    options: SummaryOptions,
 }
 
 impl Default for BufReaderSummarizer {
     fn default() -> Self {
-        Self {
-            capacity: DEFAULT_BUFFER_CAPACITY,
-            delim: DEFAULT_DELIMITER,
-        }
+        Self::new(DEFAULT_BUFFER_CAPACITY, DEFAULT_DELIMITER)
     }
 }
 
 impl BufReaderSummarizer {
     pub fn new(capacity: usize, delim: u8) -> Self {
-        Self { capacity, delim }
+        Self {
+            capacity,
+            delim,
+            options: SummaryOptions::default(),
+        }
     }
-}
 
-impl Summarizer for BufReaderSummarizer {
-    type ParserError = BufReaderParserError;
-    type SummarizerError = std::io::Error;
+    /// Keep everything `options` asks for
+    pub fn with_options(mut self, options: SummaryOptions) -> Self {
+        self.options = options;
+        self
+    }
 
-    fn summarize<P: Parser>(
+    fn reject<E: Display>(
+        &self,
+        source: &str,
+        line_number: usize,
+        error: &E,
+        line: &[u8],
+    ) -> io::Result<()> {
+        match self.options.rejects() {
+            Some(rejects) => rejects.write(
+                source,
+                &[Rejected::new(line_number, error, line)],
+                self.delim,
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// An empty summary of `source`, which gets its own random choices
+    fn new_summary(&self, source: &str) -> Summary<BufReaderParserError> {
+        self.options.for_part(source.as_bytes()).new_summary()
+    }
+
+    /// Summarize anything readable, e.g. stdin or a socket, rather than
+    /// a named file. Compressed input is decompressed as with files.
+    pub fn summarize_reader<R: Read, P: Parser>(
+        &self,
+        reader: R,
+        parser: P,
+    ) -> io::Result<Summary<BufReaderParserError>> {
+        self.summarize_buf(reader, STDIN_PATH, &parser)
+    }
+
+    /// Summarize `logfiles`, carrying on from where `checkpoint` left off
+    /// for any which have only been appended to since. Returns a summary of
+    /// each file in full, along with a checkpoint to resume from next time.
+    pub fn summarize_files_resumable<P: Parser>(
+        &self,
+        logfiles: &[String],
+        parser: P,
+        checkpoint: &Checkpoint<BufReaderParserError>,
+    ) -> io::Result<(
+        Vec<Summary<BufReaderParserError>>,
+        Checkpoint<BufReaderParserError>,
+    )> {
+        let mut summaries = Vec::with_capacity(logfiles.len());
+        let mut next = Checkpoint::default();
+        for logfile in logfiles {
+            let (summary, input) =
+                self.summarize_resumable(logfile, &parser, checkpoint.input(logfile))?;
+            summaries.push(summary);
+            next.inputs.push(input);
+        }
+        Ok((summaries, next))
+    }
+
+    /// Summarize `logfile`, skipping the part covered by `previous` if it's
+    /// the same file with more appended. A trailing line without a
+    /// delimiter is included in the summary but not the checkpoint, as it
+    /// may not have been finished. Compressed files can't be resumed part
+    /// way through, so they're only skipped if they haven't changed at all.
+    /// If a line tells us to stop, the checkpoint ends with it.
+    fn summarize_resumable<P: Parser>(
         &self,
         logfile: &str,
-    ) -> Result<Summary<Self::ParserError>, Self::SummarizerError> {
-        let file = File::open(logfile)?;
-        let mut reader = BufReader::new(file);
-        let mut summary = Summary::new();
+        parser: &P,
+        previous: Option<&InputCheckpoint<BufReaderParserError>>,
+    ) -> io::Result<(
+        Summary<BufReaderParserError>,
+        InputCheckpoint<BufReaderParserError>,
+    )> {
+        if logfile == STDIN_PATH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "stdin can't be checkpointed",
+            ));
+        }
+        let identity = FileIdentity::of(logfile)?;
+        let resume = match previous {
+            Some(previous) if previous.identity.is_prefix_of(&identity)? => Some(previous),
+            _ => None,
+        };
+        let mut reader = BufReader::new(File::open(logfile)?);
+        if Compression::detect(reader.fill_buf()?).is_some() {
+            if let Some(previous) = resume.filter(|p| p.identity.size == identity.size) {
+                return Ok((previous.summary.clone(), previous.clone()));
+            }
+            let summary = self.summarize_buf(reader, logfile, parser)?;
+            let input = InputCheckpoint {
+                offset: identity.size,
+                identity,
+                lines: 0,
+                summary: summary.clone(),
+            };
+            return Ok((summary, input));
+        }
+        let (mut summary, mut offset, mut line_number) = match resume {
+            Some(previous) => (previous.summary.clone(), previous.offset, previous.lines),
+            None => (self.new_summary(logfile), 0, 0),
+        };
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut buf = Vec::with_capacity(self.capacity);
+        loop {
+            let bytes = reader.read_until(self.delim, &mut buf)?;
+            if bytes == 0 || buf.last() != Some(&self.delim) {
+                break;
+            }
+            let stop = match parser.parse(buf.as_slice()) {
+                Ok(metadata) => {
+                    summary.accumulate(&metadata);
+                    metadata.filter == LineFilter::Stop
+                }
+                Err(err) => {
+                    summary.register_error(line_number);
+                    self.reject(logfile, line_number, &err, &buf)?;
+                    false
+                }
+            };
+            offset += bytes as u64;
+            line_number += 1;
+            buf.clear();
+            // the rest is left for next time
+            if stop {
+                break;
+            }
+        }
+        let input = InputCheckpoint {
+            identity,
+            offset,
+            lines: line_number,
+            summary: summary.clone(),
+        };
+        if !buf.is_empty() {
+            match parser.parse(buf.as_slice()) {
+                Ok(metadata) => summary.accumulate(&metadata),
+                Err(err) => {
+                    summary.register_error(line_number);
+                    self.reject(logfile, line_number, &err, &buf)?;
+                }
+            }
+        }
+        Ok((summary, input))
+    }
+
+    fn summarize_path<P: Parser>(
+        &self,
+        logfile: &str,
+        parser: &P,
+    ) -> io::Result<Summary<BufReaderParserError>> {
+        if logfile == STDIN_PATH {
+            return self.summarize_buf(io::stdin(), logfile, parser);
+        }
+        self.summarize_buf(File::open(logfile)?, logfile, parser)
+    }
+
+    fn summarize_buf<R: Read, P: Parser>(
+        &self,
+        reader: R,
+        source: &str,
+        parser: &P,
+    ) -> io::Result<Summary<BufReaderParserError>> {
+        let mut reader = decompress(reader)?;
+        let mut summary = self.new_summary(source);
         let mut buf = Vec::with_capacity(self.capacity);
         let mut line_number: usize = 0;
         loop {
@@ -50,11 +215,17 @@ impl Summarizer for BufReaderSummarizer {
             if bytes == 0 {
                 break;
             }
-            match P::parse(buf.as_slice()) {
+            match parser.parse(buf.as_slice()) {
                 Ok(metadata) => {
                     summary.accumulate(&metadata);
+                    if metadata.filter == LineFilter::Stop {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    summary.register_error(line_number);
+                    self.reject(source, line_number, &err, &buf)?;
                 }
-                Err(_) => summary.register_error(line_number),
             }
             line_number += 1;
             buf.clear();
@@ -62,3 +233,168 @@ impl Summarizer for BufReaderSummarizer {
         Ok(summary)
     }
 }
+
+impl Summarizer for BufReaderSummarizer {
+    type ParserError = BufReaderParserError;
+    type SummarizerError = io::Error;
+
+    fn summarize<P: Parser>(
+        &self,
+        logfile: &str,
+        parser: P,
+    ) -> Result<Summary<Self::ParserError>, Self::SummarizerError> {
+        self.summarize_path(logfile, &parser)
+    }
+
+    fn summarize_files<P: Parser>(
+        &self,
+        logfiles: &[String],
+        parser: P,
+    ) -> Result<Vec<Summary<Self::ParserError>>, Self::SummarizerError> {
+        logfiles
+            .iter()
+            .map(|logfile| self.summarize_path(logfile, &parser))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        JsonTypeSize, PrintOptions, RejectFile, StringParser, TimeWindow, TimestampParser,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_summarize_reader() {
+        let input = &b"{\"type\":\"a\"}\nnope\n{\"type\":\"b\"}\n{\"type\":\"a\"}"[..];
+        let summary = BufReaderSummarizer::default()
+            .summarize_reader(input, StringParser::new("type"))
+            .unwrap();
+        let json = summary.to_json_summary(&PrintOptions::default());
+        assert_eq!(json.total_errors, 1);
+        match json.type_size {
+            JsonTypeSize::Single(sizes) => {
+                assert_eq!(sizes["a"], 25);
+                assert_eq!(sizes["b"], 13);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_errors_numbered_like_rejects() {
+        let path =
+            std::env::temp_dir().join(format!("log_parser_sync_rejects_{}", std::process::id()));
+        let rejects = Arc::new(RejectFile::create(&path, true).unwrap());
+        let input = &b"{\"type\":\"a\"}\nnope\n"[..];
+        let summary = BufReaderSummarizer::default()
+            .with_options(SummaryOptions::new().with_rejects(Some(rejects)))
+            .summarize_reader(input, StringParser::new("type"))
+            .unwrap();
+        assert_eq!(summary.errors().messages()[0], "Failed to parse line 2");
+        let rejected = std::fs::read_to_string(&path).unwrap();
+        assert!(rejected.starts_with(&format!("{}:2: ", STDIN_PATH)));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_time_window() {
+        let input = &b"{\"type\":\"a\",\"ts\":1}\n{\"type\":\"a\",\"ts\":2}\n{\"type\":\"b\",\"ts\":3}\n{\"type\":\"b\",\"ts\":4}\n"[..];
+        let summarize = |ordered| {
+            let parser = TimestampParser::<StringParser>::new("type").within(TimeWindow::new(
+                Some(2),
+                Some(3),
+                ordered,
+            ));
+            let summary = BufReaderSummarizer::default()
+                .summarize_reader(input, parser)
+                .unwrap();
+            summary.to_json_summary(&PrintOptions::default())
+        };
+        let json = summarize(false);
+        assert_eq!(json.total_size, 20);
+        assert_eq!((json.filtered_lines, json.filtered_size), (3, 60));
+        // reading stops at the first line past the window
+        let json = summarize(true);
+        assert_eq!(json.total_size, 20);
+        assert_eq!((json.filtered_lines, json.filtered_size), (2, 40));
+    }
+
+    #[test]
+    fn test_resume_from_checkpoint() {
+        use std::io::Write;
+        let path =
+            std::env::temp_dir().join(format!("log_parser_resume_{}.log", std::process::id()));
+        let logfiles = vec![path.to_string_lossy().into_owned()];
+        std::fs::write(&path, "{\"type\":\"a\"}\nnope\n{\"type\":\"b\"").unwrap();
+        let summarizer = BufReaderSummarizer::default();
+        let (summaries, checkpoint) = summarizer
+            .summarize_files_resumable(&logfiles, StringParser::new("type"), &Checkpoint::default())
+            .unwrap();
+        // the unfinished line is summarized, but not checkpointed
+        assert_eq!(
+            summaries[0]
+                .to_json_summary(&PrintOptions::default())
+                .total_size,
+            24
+        );
+        assert_eq!(
+            (checkpoint.inputs[0].offset, checkpoint.inputs[0].lines),
+            (18, 2)
+        );
+        let checkpoint: Checkpoint<BufReaderParserError> =
+            serde_json::from_str(&serde_json::to_string(&checkpoint).unwrap()).unwrap();
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(b"}\nbad\n").unwrap();
+        let (summaries, resumed) = summarizer
+            .summarize_files_resumable(&logfiles, StringParser::new("type"), &checkpoint)
+            .unwrap();
+        let json = summaries[0].to_json_summary(&PrintOptions::default());
+        assert_eq!((json.total_size, json.total_errors), (26, 2));
+        let errors = &serde_json::to_value(&summaries[0]).unwrap()["errors"];
+        assert_eq!(errors["error_lines"], serde_json::json!([1, 3]));
+        assert_eq!(resumed.inputs[0].offset, 35);
+
+        // rewritten rather than appended to, so it's read from scratch
+        std::fs::write(&path, "{\"type\":\"c\"}\n").unwrap();
+        let (summaries, _) = summarizer
+            .summarize_files_resumable(&logfiles, StringParser::new("type"), &resumed)
+            .unwrap();
+        let json = summaries[0].to_json_summary(&PrintOptions::default());
+        assert_eq!((json.total_size, json.total_errors), (13, 0));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_resume_stops_early() {
+        let path =
+            std::env::temp_dir().join(format!("log_parser_resume_stop_{}.log", std::process::id()));
+        let logfiles = vec![path.to_string_lossy().into_owned()];
+        std::fs::write(
+            &path,
+            "{\"type\":\"a\",\"ts\":1}\n{\"type\":\"a\",\"ts\":5}\n{\"type\":\"b\",\"ts\":6}\n",
+        )
+        .unwrap();
+        let parser = TimestampParser::<StringParser>::new("type").within(TimeWindow::new(
+            None,
+            Some(2),
+            true,
+        ));
+        let (summaries, checkpoint) = BufReaderSummarizer::default()
+            .summarize_files_resumable(&logfiles, parser, &Checkpoint::default())
+            .unwrap();
+        let json = summaries[0].to_json_summary(&PrintOptions::default());
+        assert_eq!((json.total_size, json.filtered_lines), (20, 1));
+        assert_eq!(
+            (checkpoint.inputs[0].offset, checkpoint.inputs[0].lines),
+            (40, 2)
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,24 +1,33 @@
 use crate::ErrorSummary;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BufReaderParserError {
-    /// The lines we failed to parse
+    /// The lines we failed to parse, counting from 0. They're shown
+    /// counting from 1.
     error_lines: Vec<usize>,
 }
 
+impl BufReaderParserError {
+    /// What [ErrorSummary::display_error] prints, numbering lines like the
+    /// `file:line` annotations of a reject file
+    pub(super) fn messages(&self) -> Vec<String> {
+        match self.error_lines.as_slice() {
+            [] => vec![],
+            [line] => vec![format!("Failed to parse line {}", line + 1), "".to_owned()],
+            lines => std::iter::once("Failed to parse the following lines:".to_owned())
+                .chain(lines.iter().sorted().map(|line| format!("{}", line + 1)))
+                .chain(std::iter::once("".to_owned()))
+                .collect(),
+        }
+    }
+}
+
 impl ErrorSummary for BufReaderParserError {
     fn display_error(&self) {
-        if !self.error_lines.is_empty() {
-            if self.error_lines.len() == 1 {
-                self.red_stderr_line(format!("Failed to parse line {}", self.error_lines[0]));
-            } else {
-                self.red_stderr_line("Failed to parse the following lines:");
-                for line in self.error_lines.iter().sorted() {
-                    self.red_stderr_line(format!("{}", line));
-                }
-            }
-            self.red_stderr_line("");
+        for message in self.messages() {
+            self.red_stderr_line(message);
         }
     }
 
@@ -33,4 +42,14 @@ impl ErrorSummary for BufReaderParserError {
     fn total_errors(&self) -> usize {
         self.error_lines.len()
     }
+
+    fn error_lines(&self) -> Option<&[usize]> {
+        Some(self.error_lines.as_slice())
+    }
+
+    fn shift(&mut self, first_line: usize) {
+        for line in self.error_lines.iter_mut() {
+            *line += first_line;
+        }
+    }
 }
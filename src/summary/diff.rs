@@ -0,0 +1,278 @@
+use crate::summary::{key_headers, split_key, ErrorSummary, PrintOptions, Summary, TypeStats};
+use bytesize::ByteSize;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::{self, Write};
+use tabwriter::TabWriter;
+
+/// How a group changed between two summaries
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffStatus {
+    /// Only in the second summary
+    Added,
+    /// Only in the first summary
+    Removed,
+    /// In both
+    Changed,
+}
+
+/// The change in a single group. Shares are percentages of all the bytes or
+/// lines in their summary.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonGroupDiff {
+    /// The value of each field in the group key
+    pub key: Vec<String>,
+    pub status: DiffStatus,
+    pub size_before: usize,
+    pub size_after: usize,
+    pub size_change: i64,
+    /// Relative to the size before. Not set for added groups.
+    pub size_change_pct: Option<f64>,
+    pub size_share_before: f64,
+    pub size_share_after: f64,
+    pub lines_before: usize,
+    pub lines_after: usize,
+    pub lines_change: i64,
+    /// Relative to the lines before. Not set for added groups.
+    pub lines_change_pct: Option<f64>,
+    pub lines_share_before: f64,
+    pub lines_share_after: f64,
+}
+
+/// The differences between two summaries, e.g. of yesterday's and today's
+/// logs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonSummaryDiff {
+    /// The fields making up a composite group key. Empty for a single key.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub group_by: Vec<String>,
+    pub total_size_before: usize,
+    pub total_size_after: usize,
+    pub total_lines_before: usize,
+    pub total_lines_after: usize,
+    /// Sorted by the size of the change in bytes, largest first
+    pub groups: Vec<JsonGroupDiff>,
+}
+
+fn change(before: usize, after: usize) -> i64 {
+    after as i64 - before as i64
+}
+
+fn change_pct(before: usize, after: usize) -> Option<f64> {
+    if before == 0 {
+        None
+    } else {
+        Some(change(before, after) as f64 * 100.0 / before as f64)
+    }
+}
+
+fn share(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+fn signed_size(change: i64) -> String {
+    let sign = if change < 0 { "-" } else { "+" };
+    format!("{}{}", sign, ByteSize(change.unsigned_abs()))
+}
+
+fn signed_pct(status: DiffStatus, pct: Option<f64>) -> String {
+    match (status, pct) {
+        (DiffStatus::Added, _) => "new".to_owned(),
+        (DiffStatus::Removed, _) => "gone".to_owned(),
+        (_, Some(pct)) => format!("{:+.1}%", pct),
+        (_, None) => "".to_owned(),
+    }
+}
+
+impl<E: ErrorSummary> Summary<E> {
+    /// Compare this summary with a later one. Summaries which only kept
+    /// their heaviest groups can't be compared, as a group missing from
+    /// either may still have been seen.
+    pub fn diff<F: ErrorSummary>(
+        &self,
+        after: &Summary<F>,
+        options: &PrintOptions,
+    ) -> io::Result<JsonSummaryDiff> {
+        if self.top.is_some() || after.top.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "can't diff summaries which only kept their top groups",
+            ));
+        }
+        let before_total = self.total_stats();
+        let after_total = after.total_stats();
+        let empty = TypeStats::default();
+        let keys: HashSet<&Vec<u8>> = self
+            .type_counts
+            .keys()
+            .chain(after.type_counts.keys())
+            .collect();
+        let groups = keys
+            .into_iter()
+            .map(|key| {
+                let before = self.type_counts.get(key);
+                let status = match (before, after.type_counts.get(key)) {
+                    (None, _) => DiffStatus::Added,
+                    (_, None) => DiffStatus::Removed,
+                    _ => DiffStatus::Changed,
+                };
+                let before = before.unwrap_or(&empty);
+                let now = after.type_counts.get(key).unwrap_or(&empty);
+                JsonGroupDiff {
+                    key: split_key(key),
+                    status,
+                    size_before: before.bytes,
+                    size_after: now.bytes,
+                    size_change: change(before.bytes, now.bytes),
+                    size_change_pct: change_pct(before.bytes, now.bytes),
+                    size_share_before: share(before.bytes, before_total.bytes),
+                    size_share_after: share(now.bytes, after_total.bytes),
+                    lines_before: before.lines,
+                    lines_after: now.lines,
+                    lines_change: change(before.lines, now.lines),
+                    lines_change_pct: change_pct(before.lines, now.lines),
+                    lines_share_before: share(before.lines, before_total.lines),
+                    lines_share_after: share(now.lines, after_total.lines),
+                }
+            })
+            .sorted_by(|a, b| {
+                b.size_change
+                    .abs()
+                    .cmp(&a.size_change.abs())
+                    .then_with(|| a.key.cmp(&b.key))
+            })
+            .collect();
+        Ok(JsonSummaryDiff {
+            group_by: if self.key_width().max(after.key_width()) > 1 {
+                options.group_by.clone()
+            } else {
+                vec![]
+            },
+            total_size_before: before_total.bytes,
+            total_size_after: after_total.bytes,
+            total_lines_before: before_total.lines,
+            total_lines_after: after_total.lines,
+            groups,
+        })
+    }
+}
+
+impl JsonSummaryDiff {
+    /// Render a human readable table, biggest changes first
+    pub fn to_table(&self, options: &PrintOptions) -> Result<String, std::fmt::Error> {
+        let width = self.groups.iter().map(|g| g.key.len()).max().unwrap_or(1);
+        let mut rows = vec![format!(
+            "{}\tSize\tChange\t%\tLines\tChange\t%\tSize share\tLine share",
+            key_headers(options, width)
+        )];
+        for group in self.groups.iter() {
+            rows.push(format!(
+                "{}{}\t{}\t{}\t{}\t{}\t{:+}\t{}\t{:.1}% -> {:.1}%\t{:.1}% -> {:.1}%",
+                group.key.join("\t"),
+                "\t".repeat(width - group.key.len()),
+                ByteSize(group.size_after as u64),
+                signed_size(group.size_change),
+                signed_pct(group.status, group.size_change_pct),
+                group.lines_after,
+                group.lines_change,
+                signed_pct(group.status, group.lines_change_pct),
+                group.size_share_before,
+                group.size_share_after,
+                group.lines_share_before,
+                group.lines_share_after,
+            ));
+        }
+        let size_change = change(self.total_size_before, self.total_size_after);
+        let lines_change = change(self.total_lines_before, self.total_lines_after);
+        rows.push(format!(
+            "Total:{}\t{}\t{}\t{}\t{}\t{:+}\t{}",
+            "\t".repeat(width - 1),
+            ByteSize(self.total_size_after as u64),
+            signed_size(size_change),
+            signed_pct(
+                DiffStatus::Changed,
+                change_pct(self.total_size_before, self.total_size_after)
+            ),
+            self.total_lines_after,
+            lines_change,
+            signed_pct(
+                DiffStatus::Changed,
+                change_pct(self.total_lines_before, self.total_lines_after)
+            ),
+        ));
+        let mut tw = TabWriter::new(vec![]);
+        tw.write_all(rows.join("\n").as_bytes())
+            .map_err(|_| std::fmt::Error)?;
+        Ok(String::from_utf8_lossy(&tw.into_inner().map_err(|_| std::fmt::Error)?).to_string())
+    }
+
+    pub fn print(&self, options: &PrintOptions) {
+        let payload = if options.json {
+            serde_json::to_string_pretty(self).expect("Failed to serialize json diff")
+        } else {
+            self.to_table(options).expect("Failed to format diff table")
+        };
+        println!("{}", payload);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::summary::tests::{summary, NoErrors};
+    use crate::summary::{TopBy, TopK};
+
+    #[test]
+    fn test_diff() {
+        let before = summary(&[(b"a", 10), (b"a", 10), (b"b", 20), (b"gone", 60)]);
+        let after = summary(&[(b"a", 30), (b"b", 20), (b"new", 50)]);
+        let diff = before.diff(&after, &PrintOptions::default()).unwrap();
+        assert_eq!((diff.total_size_before, diff.total_size_after), (100, 100));
+        let keys: Vec<_> = diff.groups.iter().map(|g| g.key[0].as_str()).collect();
+        assert_eq!(keys, vec!["gone", "new", "a", "b"]);
+
+        let gone = &diff.groups[0];
+        assert_eq!(gone.status, DiffStatus::Removed);
+        assert_eq!(
+            (gone.size_change, gone.size_change_pct),
+            (-60, Some(-100.0))
+        );
+        let new = &diff.groups[1];
+        assert_eq!(new.status, DiffStatus::Added);
+        assert_eq!(new.size_change_pct, None);
+        let a = &diff.groups[2];
+        assert_eq!(a.status, DiffStatus::Changed);
+        assert_eq!((a.size_change, a.size_change_pct), (10, Some(50.0)));
+        assert_eq!((a.lines_change, a.lines_change_pct), (-1, Some(-50.0)));
+        assert_eq!((a.size_share_before, a.size_share_after), (20.0, 30.0));
+        assert_eq!(
+            (a.lines_share_before, a.lines_share_after),
+            (50.0, 100.0 / 3.0)
+        );
+
+        let table = diff.to_table(&PrintOptions::default()).unwrap();
+        assert!(table.contains("gone"));
+        assert!(table.contains("+10 B"));
+    }
+
+    #[test]
+    fn test_diff_top_keys() {
+        let exact = summary(&[(b"a", 10)]);
+        let mut bounded = Summary::<NoErrors>::with_top_k(Some(TopK::new(1, TopBy::Bytes)));
+        bounded.combine(exact.clone());
+        let options = PrintOptions::default();
+        for err in [
+            bounded.diff(&exact, &options).unwrap_err(),
+            exact.diff(&bounded, &options).unwrap_err(),
+            bounded.diff(&bounded, &options).unwrap_err(),
+        ] {
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
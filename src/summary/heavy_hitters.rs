@@ -0,0 +1,223 @@
+use crate::summary::{deserialize_keyed, serialize_keyed, split_key};
+use crate::TypeStats;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// What heavy hitters are ranked by
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TopBy {
+    Bytes,
+    Lines,
+}
+
+impl FromStr for TopBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bytes" => Ok(TopBy::Bytes),
+            "lines" => Ok(TopBy::Lines),
+            _ => Err(format!("unknown ranking `{}`, expected bytes or lines", s)),
+        }
+    }
+}
+
+/// Only keep the `k` heaviest groups, in bounded memory
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TopK {
+    pub k: usize,
+    pub by: TopBy,
+}
+
+impl TopK {
+    pub fn new(k: usize, by: TopBy) -> Self {
+        Self { k, by }
+    }
+
+    fn weight(&self, bytes: usize, lines: usize) -> usize {
+        match self.by {
+            TopBy::Bytes => bytes,
+            TopBy::Lines => lines,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonTopKeys {
+    pub k: usize,
+    pub by: TopBy,
+    /// Any group not listed has at most this weight
+    pub error: usize,
+    /// Sorted by weight, heaviest first
+    pub keys: Vec<JsonTopKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonTopKey {
+    /// The value of each field in the group key
+    pub key: Vec<String>,
+    /// The group's weight is at least this
+    pub lower: usize,
+    /// and at most this
+    pub upper: usize,
+}
+
+/// A weighted Misra-Gries summary. Counts only ever undercount, by at most
+/// `error`, and two summaries can be merged with the same guarantee, so
+/// mappers can each keep one. Between prunes there are up to `2k` counters,
+/// so pruning costs O(1) per line amortised.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct HeavyHitters {
+    top_k: TopK,
+    #[serde(
+        serialize_with = "serialize_keyed",
+        deserialize_with = "deserialize_keyed"
+    )]
+    counts: HashMap<Vec<u8>, usize>,
+    /// How far below its true weight any count may be
+    error: usize,
+    /// Every line counted, whether or not its group is still tracked
+    total: TypeStats,
+}
+
+impl HeavyHitters {
+    pub(super) fn new(top_k: TopK) -> Self {
+        Self {
+            top_k,
+            counts: HashMap::new(),
+            error: 0,
+            total: TypeStats::default(),
+        }
+    }
+
+    pub(super) fn total(&self) -> &TypeStats {
+        &self.total
+    }
+
+    pub(super) fn keys(&self) -> impl Iterator<Item = &Vec<u8>> {
+        self.counts.keys()
+    }
+
+    pub(super) fn accumulate(&mut self, type_name: &[u8], bytes: usize) {
+        self.total.add(bytes);
+        let weight = self.top_k.weight(bytes, 1);
+        match self.counts.get_mut(type_name) {
+            Some(count) => *count += weight,
+            None => {
+                self.counts.insert(type_name.to_vec(), weight);
+                self.maybe_prune();
+            }
+        }
+    }
+
+    /// Add a group counted exactly, e.g. by an unbounded summary
+    pub(super) fn add_group(&mut self, key: Vec<u8>, stats: &TypeStats) {
+        self.total.combine(stats);
+        *self.counts.entry(key).or_default() += self.top_k.weight(stats.bytes, stats.lines);
+        self.maybe_prune();
+    }
+
+    /// Merge in another summary, keeping the smaller `k` if they differ
+    pub(super) fn combine(&mut self, other: Self) {
+        debug_assert_eq!(self.top_k.by, other.top_k.by);
+        self.top_k.k = self.top_k.k.min(other.top_k.k);
+        self.total.combine(&other.total);
+        self.error += other.error;
+        for (key, count) in other.counts {
+            *self.counts.entry(key).or_default() += count;
+        }
+        self.maybe_prune();
+    }
+
+    fn maybe_prune(&mut self) {
+        if self.counts.len() > 2 * self.top_k.k {
+            self.prune();
+        }
+    }
+
+    /// Subtract the `k + 1`th largest count from every counter, dropping
+    /// those left empty, so at most `k` remain
+    fn prune(&mut self) {
+        let k = self.top_k.k;
+        if self.counts.len() <= k {
+            return;
+        }
+        let mut counts: Vec<usize> = self.counts.values().copied().collect();
+        let cut = *counts.select_nth_unstable_by(k, |a, b| b.cmp(a)).1;
+        self.counts.retain(|_, count| {
+            *count -= cut.min(*count);
+            *count > 0
+        });
+        self.error += cut;
+    }
+
+    /// The heaviest `k` groups with their count, heaviest first
+    pub(super) fn top(&self) -> Vec<(&[u8], usize)> {
+        let mut top: Vec<_> = self
+            .counts
+            .iter()
+            .map(|(key, count)| (key.as_slice(), *count))
+            .collect();
+        top.sort_by(|(a_key, a), (b_key, b)| b.cmp(a).then_with(|| a_key.cmp(b_key)));
+        top.truncate(self.top_k.k);
+        top
+    }
+
+    pub(super) fn by(&self) -> TopBy {
+        self.top_k.by
+    }
+
+    pub(super) fn error(&self) -> usize {
+        self.error
+    }
+
+    pub(super) fn to_json(&self) -> JsonTopKeys {
+        JsonTopKeys {
+            k: self.top_k.k,
+            by: self.top_k.by,
+            error: self.error,
+            keys: self
+                .top()
+                .into_iter()
+                .map(|(key, count)| JsonTopKey {
+                    key: split_key(key),
+                    lower: count,
+                    upper: count + self.error,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heavy_hitters() {
+        let top_k = TopK::new(2, TopBy::Bytes);
+        let mut a = HeavyHitters::new(top_k);
+        let mut b = HeavyHitters::new(top_k);
+        for i in 0..100 {
+            a.accumulate(b"big", 100);
+            b.accumulate(b"medium", 50);
+            // plenty of distinct small groups to force pruning
+            a.accumulate(format!("a{}", i).as_bytes(), 1);
+            b.accumulate(format!("b{}", i).as_bytes(), 1);
+        }
+        assert!(a.counts.len() <= 4 && b.counts.len() <= 4);
+        a.combine(b);
+        let json = a.to_json();
+        let keys: Vec<_> = json.keys.iter().map(|k| k.key[0].as_str()).collect();
+        assert_eq!(keys, vec!["big", "medium"]);
+        for (key, truth) in json.keys.iter().zip([10_000, 5_000]) {
+            assert!(key.lower <= truth && truth <= key.upper);
+        }
+        // each prune takes `error` from at least k + 1 counters
+        let tracked: usize = a.counts.values().sum();
+        assert!(json.error * 3 <= a.total().bytes - tracked);
+        assert_eq!(a.total().bytes, 15_200);
+    }
+}
@@ -0,0 +1,1188 @@
+use crate::{LineFilter, LogLineMetadata, KEY_SEPARATOR};
+use bytesize::ByteSize;
+use colored::Colorize;
+pub use diff::{DiffStatus, JsonGroupDiff, JsonSummaryDiff};
+use heavy_hitters::HeavyHitters;
+pub use heavy_hitters::{JsonTopKey, JsonTopKeys, TopBy, TopK};
+pub use hyperloglog::{HyperLogLog, HLL_RELATIVE_ERROR};
+use itertools::Itertools;
+use samples::Samples;
+pub use samples::{JsonSamples, Sampling};
+pub use saved::{SavedErrors, SummaryFile, SUMMARY_FORMAT_VERSION};
+pub use schema::{FieldSchema, ValueKind};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+pub use size_sketch::{SizeSketch, SKETCH_RELATIVE_ACCURACY};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::io::Write;
+use std::ops::Deref;
+use std::str::FromStr;
+use tabwriter::TabWriter;
+use time_buckets::TimeBuckets;
+pub use time_buckets::{BucketStats, JsonBucket, JsonBucketGroup, TimeBucket};
+pub use type_stats::{JsonTypeStats, TypeStats};
+
+mod diff;
+mod heavy_hitters;
+mod hyperloglog;
+mod samples;
+mod saved;
+mod schema;
+mod size_sketch;
+mod time_buckets;
+mod type_stats;
+
+/// Don't use references for the keys as this would enforce any file buffer
+/// to live for 'static ==> no buffered loading
+type TypeCountMap = HashMap<Vec<u8>, TypeStats>;
+
+/// A public facing JSON type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonSummary {
+    /// The total file size
+    pub total_size: usize,
+    /// The fields making up a composite group key. Empty for a single key.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub group_by: Vec<String>,
+    /// All types and aggregate sizes in bytes
+    pub type_size: JsonTypeSize,
+    /// Line counts and sizes for each type. Composite keys carry these
+    /// inline in [JsonTypeSize] instead.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub type_stats: HashMap<String, JsonTypeStats>,
+    /// The total number of lines which returned errors
+    pub total_errors: usize,
+    /// Sizes per group over time, if asked for and lines had timestamps
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buckets: Vec<JsonBucket>,
+    /// Lines which parsed but were filtered out, e.g. by time
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub filtered_lines: usize,
+    /// The size of the filtered lines in bytes
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub filtered_size: usize,
+    /// Lines which parsed but whose type was excluded
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub excluded_lines: usize,
+    /// The size of the excluded lines in bytes
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub excluded_size: usize,
+    /// The heaviest groups, if only they were kept. `type_size` is then
+    /// empty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_keys: Option<JsonTopKeys>,
+    /// The field whose distinct values were counted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distinct_field: Option<String>,
+    /// Estimated distinct values of `distinct_field` per group, most first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub distinct: Vec<JsonDistinct>,
+    /// The inferred schema of each group, if asked for
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schemas: Vec<JsonSchemaGroup>,
+    /// Example lines from each group, if asked for
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub samples: Vec<JsonSamples>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonSchemaGroup {
+    /// The value of each field in the group key
+    pub key: Vec<String>,
+    /// A JSON Schema describing every line seen in the group
+    pub schema: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonDistinct {
+    /// The value of each field in the group key
+    pub key: Vec<String>,
+    pub estimate: u64,
+    /// One standard error either side of the estimate
+    pub standard_error: u64,
+}
+
+/// The version of JSON Schema inferred schemas are written in
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+/// A [JsonSummary] for each file summarized together, plus their total
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonFilesSummary {
+    pub files: Vec<JsonFileSummary>,
+    pub total: JsonSummary,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonFileSummary {
+    pub file: String,
+    #[serde(flatten)]
+    pub summary: JsonSummary,
+}
+
+/// The aggregate sizes in a [JsonSummary], shaped by the [GroupLayout]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JsonTypeSize {
+    /// A single group key, keyed on its value
+    Single(HashMap<String, usize>),
+    /// One entry per combination of composite key values
+    Flat(Vec<JsonGroup>),
+    /// Keyed on the first field of a composite key, then the next and so on
+    Nested(HashMap<String, JsonNestedGroup>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonGroup {
+    /// The value of each field in the composite key
+    pub key: Vec<String>,
+    #[serde(flatten)]
+    pub stats: JsonTypeStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonNestedGroup {
+    /// The stats of this group and everything beneath it
+    #[serde(flatten)]
+    pub stats: JsonTypeStats,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub groups: HashMap<String, JsonNestedGroup>,
+}
+
+/// How composite group keys are laid out when printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupLayout {
+    /// One row per distinct combination of values
+    Flat,
+    /// Grouped by each field in turn, with subtotals
+    Nested,
+}
+
+impl FromStr for GroupLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flat" => Ok(GroupLayout::Flat),
+            "nested" => Ok(GroupLayout::Nested),
+            _ => Err(format!("unknown layout `{}`, expected flat or nested", s)),
+        }
+    }
+}
+
+/// How to render a [Summary]
+#[derive(Debug, Clone)]
+pub struct PrintOptions {
+    /// Print JSON rather than a table
+    pub json: bool,
+    pub layout: GroupLayout,
+    /// The names of the fields making up the group key, used as headers
+    pub group_by: Vec<String>,
+    /// Break down the summary by file as well as the total, when several
+    /// files were summarized
+    pub per_file: bool,
+    /// Also break down each group over time
+    pub bucket: Option<TimeBucket>,
+    /// The field whose distinct values were counted, used as a header
+    pub distinct: Option<String>,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            json: false,
+            layout: GroupLayout::Flat,
+            group_by: vec![],
+            per_file: false,
+            bucket: None,
+            distinct: None,
+        }
+    }
+}
+
+/// Composite keys arranged by each field in turn
+#[derive(Debug, Default)]
+struct GroupTree {
+    stats: TypeStats,
+    groups: HashMap<String, GroupTree>,
+}
+
+impl GroupTree {
+    fn to_json(&self) -> HashMap<String, JsonNestedGroup> {
+        self.groups
+            .iter()
+            .map(|(k, v)| {
+                let group = JsonNestedGroup {
+                    stats: (&v.stats).into(),
+                    groups: v.to_json(),
+                };
+                (k.clone(), group)
+            })
+            .collect()
+    }
+}
+
+/// An aggregated type string count. Unlike [JsonSummary] this serializes
+/// losslessly, so it can be saved and combined with others later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Summary<E: ErrorSummary> {
+    #[serde(
+        serialize_with = "serialize_keyed",
+        deserialize_with = "deserialize_keyed"
+    )]
+    type_counts: TypeCountMap,
+    errors: E,
+    /// Only kept when asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    buckets: Option<TimeBuckets>,
+    /// Lines which parsed but aren't counted by type
+    #[serde(default, skip_serializing_if = "is_zero")]
+    filtered_lines: usize,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    filtered_bytes: usize,
+    /// Lines which parsed but whose type wasn't wanted
+    #[serde(default, skip_serializing_if = "is_zero")]
+    excluded_lines: usize,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    excluded_bytes: usize,
+    /// Replaces `type_counts` when memory is bounded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    top: Option<HeavyHitters>,
+    /// Sketches of another field's values per group
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_keyed",
+        deserialize_with = "deserialize_keyed"
+    )]
+    distinct: HashMap<Vec<u8>, HyperLogLog>,
+    /// The fields seen in each group's documents
+    #[serde(
+        default,
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_keyed",
+        deserialize_with = "deserialize_keyed"
+    )]
+    schemas: HashMap<Vec<u8>, FieldSchema>,
+    /// Example lines from each group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    samples: Option<Samples>,
+}
+
+/// Group keys are bytes, but are almost always UTF-8, so keep them readable
+/// where we can
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SerializedKey {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl SerializedKey {
+    fn new(key: &[u8]) -> Self {
+        match std::str::from_utf8(key) {
+            Ok(key) => SerializedKey::Text(key.to_owned()),
+            Err(_) => SerializedKey::Bytes(key.to_vec()),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            SerializedKey::Text(key) => key.into_bytes(),
+            SerializedKey::Bytes(key) => key,
+        }
+    }
+}
+
+/// Written as a list of `[key, value]` pairs, as JSON keys must be strings
+fn serialize_keyed<S: Serializer, V: Serialize>(
+    map: &HashMap<Vec<u8>, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(map.iter().map(|(k, v)| (SerializedKey::new(k), v)))
+}
+
+fn deserialize_keyed<'de, D: Deserializer<'de>, V: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<HashMap<Vec<u8>, V>, D::Error> {
+    let entries: Vec<(SerializedKey, V)> = Deserialize::deserialize(deserializer)?;
+    Ok(entries
+        .into_iter()
+        .map(|(k, v)| (k.into_bytes(), v))
+        .collect())
+}
+
+impl<E: ErrorSummary> Default for Summary<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: ErrorSummary> Display for Summary<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_table(&PrintOptions::default())?)
+    }
+}
+
+/// Split a composite key back in to the value of each field
+fn split_key(key: &[u8]) -> Vec<String> {
+    key.split(|&b| b == KEY_SEPARATOR)
+        .map(|part| String::from_utf8_lossy(part).to_string())
+        .collect()
+}
+
+/// The table headers for each field of a flat group key
+fn key_headers(options: &PrintOptions, width: usize) -> String {
+    if options.group_by.len() == width {
+        options.group_by.join("\t")
+    } else {
+        format!("Type{}", "\t".repeat(width - 1))
+    }
+}
+
+/// The stat columns of a table row
+fn stats_columns(stats: &TypeStats) -> String {
+    let min = if stats.lines == 0 { 0 } else { stats.min };
+    format!(
+        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        ByteSize(stats.bytes as u64),
+        stats.lines,
+        ByteSize(min as u64),
+        ByteSize(stats.max as u64),
+        ByteSize(stats.mean().round() as u64),
+        ByteSize(stats.quantile(0.5).round() as u64),
+        ByteSize(stats.quantile(0.9).round() as u64),
+        ByteSize(stats.quantile(0.99).round() as u64)
+    )
+}
+
+impl<E: ErrorSummary> Summary<E> {
+    pub fn new() -> Self {
+        Self::with_top_k(None)
+    }
+
+    /// Only keep the heaviest groups, if `top_k` is given, rather than
+    /// every one seen. Buckets, samples, schemas and distinct values are
+    /// still kept for every group.
+    pub fn with_top_k(top_k: Option<TopK>) -> Self {
+        Summary {
+            type_counts: HashMap::new(),
+            errors: E::default(),
+            buckets: None,
+            filtered_lines: 0,
+            filtered_bytes: 0,
+            excluded_lines: 0,
+            excluded_bytes: 0,
+            top: top_k.map(HeavyHitters::new),
+            distinct: HashMap::new(),
+            schemas: HashMap::new(),
+            samples: None,
+        }
+    }
+
+    /// Also count lines per minute, if `bucketing`, so they can be printed
+    /// in time buckets
+    pub fn with_buckets(mut self, bucketing: bool) -> Self {
+        self.buckets = if bucketing {
+            Some(TimeBuckets::default())
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Also keep a few example lines from each group, if `sampling` is given
+    pub fn with_samples(mut self, sampling: Option<Sampling>) -> Self {
+        self.samples = sampling.map(Samples::new);
+        self
+    }
+
+    pub fn register_error(&mut self, error: usize) {
+        self.errors.accumulate(error);
+    }
+
+    pub fn errors(&self) -> &E {
+        &self.errors
+    }
+
+    pub fn errors_mut(&mut self) -> &mut E {
+        &mut self.errors
+    }
+
+    /// Convert to the format saved to disk, noting that any line numbers
+    /// are from `source`
+    pub fn to_saved(&self, source: &str) -> Summary<SavedErrors> {
+        let mut errors = SavedErrors {
+            total: self.errors.total_errors(),
+            ..SavedErrors::default()
+        };
+        if let Some(lines) = self.errors.error_lines() {
+            if !lines.is_empty() {
+                errors.lines.insert(source.to_owned(), lines.to_vec());
+            }
+        }
+        Summary {
+            type_counts: self.type_counts.clone(),
+            errors,
+            buckets: self.buckets.clone(),
+            filtered_lines: self.filtered_lines,
+            filtered_bytes: self.filtered_bytes,
+            excluded_lines: self.excluded_lines,
+            excluded_bytes: self.excluded_bytes,
+            top: self.top.clone(),
+            distinct: self.distinct.clone(),
+            schemas: self.schemas.clone(),
+            samples: self.samples.clone(),
+        }
+    }
+
+    /// The stats of every line which parsed
+    fn total_stats(&self) -> TypeStats {
+        let mut total = match &self.top {
+            Some(top) => top.total().clone(),
+            None => TypeStats::default(),
+        };
+        for stats in self.type_counts.values() {
+            total.combine(stats);
+        }
+        total
+    }
+
+    /// The total file size
+    fn total_size(&self) -> usize {
+        let top = self.top.as_ref().map_or(0, |top| top.total().bytes);
+        top + self
+            .type_counts
+            .values()
+            .map(|stats| stats.bytes)
+            .sum::<usize>()
+    }
+
+    /// The number of fields in the group key
+    fn key_width(&self) -> usize {
+        self.type_counts
+            .keys()
+            .chain(self.top.iter().flat_map(HeavyHitters::keys))
+            .map(|k| k.iter().filter(|&&b| b == KEY_SEPARATOR).count() + 1)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Build a tree of groups keyed on each field of the key in turn
+    fn nested_groups(&self) -> GroupTree {
+        let mut root = GroupTree::default();
+        for (k, v) in self.type_counts.iter() {
+            root.stats.combine(v);
+            let mut node = &mut root;
+            for part in split_key(k) {
+                node = node.groups.entry(part).or_default();
+                node.stats.combine(v);
+            }
+        }
+        root
+    }
+
+    pub fn print(&self, options: &PrintOptions) {
+        let payload = if options.json {
+            self.to_json(options)
+        } else {
+            // here we output any errors to stderr
+            // can be more descriptive than the json e.g. line numbers
+            self.errors.display_error();
+            self.to_table(options)
+                .expect("Failed to format summary table")
+        };
+        println!("{}", payload);
+    }
+
+    /// Print a summary which is still changing. A table replaces the
+    /// previous one on the terminal, whereas JSON is printed on a single
+    /// line so each snapshot can be read as it arrives.
+    pub fn print_snapshot(&self, options: &PrintOptions) {
+        if options.json {
+            let payload = serde_json::to_string(&self.to_json_summary(options))
+                .expect("Failed to serialize json summary");
+            println!("{}", payload);
+            return;
+        }
+        let table = self
+            .to_table(options)
+            .expect("Failed to format summary table");
+        // clear the screen and move the cursor to the top left
+        print!("\x1b[2J\x1b[H");
+        let errors = self.errors.total_errors();
+        if errors > 0 {
+            self.errors
+                .red_stderr_line(format!("{} lines failed to parse", errors));
+        }
+        println!("{}", table);
+    }
+
+    /// Print the total of several files' summaries. Parse errors are
+    /// reported per file, as their line numbers are relative to it.
+    pub fn print_files(files: Vec<(String, Self)>, options: &PrintOptions) {
+        if files.len() == 1 && !options.per_file {
+            if let Some((_, summary)) = files.into_iter().next() {
+                summary.print(options);
+            }
+            return;
+        }
+        let mut total = Self::new();
+        if options.json {
+            let mut json_files = Vec::with_capacity(files.len());
+            for (file, summary) in files {
+                if options.per_file {
+                    json_files.push(JsonFileSummary {
+                        file,
+                        summary: summary.to_json_summary(options),
+                    });
+                }
+                total.combine(summary);
+            }
+            let payload = if options.per_file {
+                serde_json::to_string_pretty(&JsonFilesSummary {
+                    files: json_files,
+                    total: total.to_json_summary(options),
+                })
+                .expect("Failed to serialize json summary")
+            } else {
+                total.to_json(options)
+            };
+            println!("{}", payload);
+            return;
+        }
+        for (file, summary) in files {
+            if options.per_file {
+                println!("==> {} <==", file);
+                summary.print(options);
+            } else if summary.errors.total_errors() > 0 {
+                summary.errors.red_stderr_line(format!("{}:", file));
+                summary.errors.display_error();
+            }
+            total.combine(summary);
+        }
+        if options.per_file {
+            println!("==> total <==");
+        }
+        println!(
+            "{}",
+            total
+                .to_table(options)
+                .expect("Failed to format summary table")
+        );
+    }
+
+    /// Render a human readable table, sorted by size desc
+    pub fn to_table(&self, options: &PrintOptions) -> Result<String, std::fmt::Error> {
+        fn tw_write<L: std::io::Write, S: Deref<Target = str>>(
+            tw: &mut TabWriter<L>,
+            s: S,
+        ) -> Result<usize, std::fmt::Error> {
+            tw.write(s.as_bytes()).map_err(|_| std::fmt::Error)
+        }
+        fn write_nested<L: std::io::Write>(
+            tw: &mut TabWriter<L>,
+            tree: &GroupTree,
+            depth: usize,
+        ) -> Result<(), std::fmt::Error> {
+            let sorted = tree
+                .groups
+                .iter()
+                .sorted_by(|(_, a), (_, b)| b.stats.bytes.cmp(&a.stats.bytes));
+            for (k, v) in sorted {
+                tw_write(
+                    tw,
+                    format!(
+                        "{:indent$}{}\t{}\n",
+                        "",
+                        k,
+                        stats_columns(&v.stats),
+                        indent = depth * 2
+                    ),
+                )?;
+                write_nested(tw, v, depth + 1)?;
+            }
+            Ok(())
+        }
+        const STATS_HEADER: &str = "Size\tLines\tMin\tMax\tMean\tP50\tP90\tP99";
+        let mut tw = TabWriter::new(vec![]);
+        let width = self.key_width();
+        let nested = width > 1 && options.layout == GroupLayout::Nested && self.top.is_none();
+        // nested keys share a single column
+        let key_columns = if nested { 1 } else { width };
+        if let Some(top) = &self.top {
+            let (header, total) = match top.by() {
+                TopBy::Bytes => ("Size", ByteSize(self.total_size() as u64).to_string()),
+                TopBy::Lines => ("Lines", self.total_stats().lines.to_string()),
+            };
+            let format = |count: usize| match top.by() {
+                TopBy::Bytes => ByteSize(count as u64).to_string(),
+                TopBy::Lines => count.to_string(),
+            };
+            tw_write(
+                &mut tw,
+                format!("{}\t{}\tUp to\n", key_headers(options, width), header),
+            )?;
+            for (k, count) in top.top() {
+                tw_write(
+                    &mut tw,
+                    format!(
+                        "{}\t{}\t{}\n",
+                        split_key(k).join("\t"),
+                        format(count),
+                        format(count + top.error())
+                    ),
+                )?;
+            }
+            tw_write(
+                &mut tw,
+                format!("Total:{}\t{}", "\t".repeat(width - 1), total),
+            )?;
+        } else if nested {
+            let headers = if options.group_by.is_empty() {
+                "Type".to_owned()
+            } else {
+                options.group_by.join(" > ")
+            };
+            tw_write(&mut tw, format!("{}\t{}\n", headers, STATS_HEADER))?;
+            write_nested(&mut tw, &self.nested_groups(), 0)?;
+            tw_write(
+                &mut tw,
+                format!("Total:\t{}", stats_columns(&self.total_stats())),
+            )?;
+        } else {
+            tw_write(
+                &mut tw,
+                format!("{}\t{}\n", key_headers(options, width), STATS_HEADER),
+            )?;
+            let sorted = self
+                .type_counts
+                .iter()
+                .sorted_by(|(_, a), (_, b)| b.bytes.cmp(&a.bytes));
+            for (k, v) in sorted {
+                tw_write(
+                    &mut tw,
+                    format!("{}\t{}\n", split_key(k).join("\t"), stats_columns(v)),
+                )?;
+            }
+            tw_write(
+                &mut tw,
+                format!(
+                    "Total:{}\t{}",
+                    "\t".repeat(width - 1),
+                    stats_columns(&self.total_stats())
+                ),
+            )?;
+        }
+        if self.filtered_lines > 0 {
+            tw_write(
+                &mut tw,
+                format!(
+                    "\nFiltered:{}\t{}\t{}",
+                    "\t".repeat(key_columns - 1),
+                    ByteSize(self.filtered_bytes as u64),
+                    self.filtered_lines
+                ),
+            )?;
+        }
+        if self.excluded_lines > 0 {
+            tw_write(
+                &mut tw,
+                format!(
+                    "\nExcluded:{}\t{}\t{}",
+                    "\t".repeat(key_columns - 1),
+                    ByteSize(self.excluded_bytes as u64),
+                    self.excluded_lines
+                ),
+            )?;
+        }
+        if !self.distinct.is_empty() {
+            tw_write(
+                &mut tw,
+                format!(
+                    "\n\n{}\tDistinct {}\tError",
+                    key_headers(options, width),
+                    options.distinct.as_deref().unwrap_or_default()
+                ),
+            )?;
+            for distinct in self.distinct_json() {
+                tw_write(
+                    &mut tw,
+                    format!(
+                        "\n{}\t{}\t±{}",
+                        distinct.key.join("\t"),
+                        distinct.estimate,
+                        distinct.standard_error
+                    ),
+                )?;
+            }
+        }
+        if !self.schemas.is_empty() {
+            tw_write(
+                &mut tw,
+                format!("\n\n{}\tField\tTypes\tPresent", key_headers(options, width)),
+            )?;
+            let sorted = self.schemas.iter().sorted_by(|(a, _), (b, _)| a.cmp(b));
+            for (k, schema) in sorted {
+                let key = split_key(k).join("\t");
+                for row in schema.rows() {
+                    tw_write(
+                        &mut tw,
+                        format!(
+                            "\n{}\t{}\t{}\t{:.0}%",
+                            key,
+                            row.path,
+                            row.kinds.join(", "),
+                            row.presence * 100.0
+                        ),
+                    )?;
+                }
+            }
+        }
+        let samples = self.samples_json();
+        if !samples.is_empty() {
+            tw_write(
+                &mut tw,
+                format!("\n\n{}\tSample", key_headers(options, width)),
+            )?;
+            for group in samples {
+                let key = group.key.join("\t");
+                for line in group.lines {
+                    tw_write(&mut tw, format!("\n{}\t{}", key, line))?;
+                }
+            }
+        }
+        let buckets = self.buckets.as_ref().filter(|buckets| !buckets.is_empty());
+        if let (Some(bucket), Some(buckets)) = (options.bucket, buckets) {
+            tw_write(
+                &mut tw,
+                format!("\n\nTime\t{}\tSize\tLines", key_headers(options, width)),
+            )?;
+            for row in buckets.to_rows(bucket) {
+                tw_write(&mut tw, format!("\n{}", row))?;
+            }
+        }
+        Ok(String::from_utf8_lossy(&tw.into_inner().map_err(|_| std::fmt::Error)?).to_string())
+    }
+
+    /// Convert this to the public facing json type
+    pub fn to_json_summary(&self, options: &PrintOptions) -> JsonSummary {
+        let width = self.key_width();
+        let mut type_stats = HashMap::new();
+        let type_size = if width == 1 {
+            let mut type_size = HashMap::new();
+            for (k, v) in self.type_counts.iter() {
+                let key = String::from_utf8_lossy(k).to_string();
+                type_size.insert(key.clone(), v.bytes);
+                type_stats.insert(key, v.into());
+            }
+            JsonTypeSize::Single(type_size)
+        } else {
+            match options.layout {
+                GroupLayout::Flat => JsonTypeSize::Flat(
+                    self.type_counts
+                        .iter()
+                        .sorted_by(|(_, a), (_, b)| b.bytes.cmp(&a.bytes))
+                        .map(|(k, v)| JsonGroup {
+                            key: split_key(k),
+                            stats: v.into(),
+                        })
+                        .collect(),
+                ),
+                GroupLayout::Nested => JsonTypeSize::Nested(self.nested_groups().to_json()),
+            }
+        };
+        JsonSummary {
+            total_size: self.total_size(),
+            group_by: if width > 1 {
+                options.group_by.clone()
+            } else {
+                vec![]
+            },
+            type_size,
+            type_stats,
+            total_errors: self.errors.total_errors(),
+            buckets: match (options.bucket, &self.buckets) {
+                (Some(bucket), Some(buckets)) => buckets.to_json(bucket),
+                _ => vec![],
+            },
+            filtered_lines: self.filtered_lines,
+            filtered_size: self.filtered_bytes,
+            excluded_lines: self.excluded_lines,
+            excluded_size: self.excluded_bytes,
+            top_keys: self.top.as_ref().map(HeavyHitters::to_json),
+            distinct_field: options
+                .distinct
+                .clone()
+                .filter(|_| !self.distinct.is_empty()),
+            distinct: self.distinct_json(),
+            schemas: self.json_schemas(),
+            samples: self.samples_json(),
+        }
+    }
+
+    /// Distinct value estimates per group, most first
+    fn distinct_json(&self) -> Vec<JsonDistinct> {
+        self.distinct
+            .iter()
+            .map(|(k, sketch)| {
+                let estimate = sketch.estimate();
+                JsonDistinct {
+                    key: split_key(k),
+                    estimate: estimate.round() as u64,
+                    standard_error: (estimate * HLL_RELATIVE_ERROR).round() as u64,
+                }
+            })
+            .sorted_by(|a, b| b.estimate.cmp(&a.estimate).then_with(|| a.key.cmp(&b.key)))
+            .collect()
+    }
+
+    fn samples_json(&self) -> Vec<JsonSamples> {
+        self.samples
+            .as_ref()
+            .map(Samples::to_json)
+            .unwrap_or_default()
+    }
+
+    /// The inferred schema of each group as a JSON Schema, sorted by key
+    pub fn json_schemas(&self) -> Vec<JsonSchemaGroup> {
+        self.schemas
+            .iter()
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(k, schema)| {
+                let key = split_key(k);
+                let mut schema = schema.to_json_schema();
+                if let serde_json::Value::Object(schema) = &mut schema {
+                    schema.insert("$schema".to_owned(), JSON_SCHEMA_DIALECT.to_owned().into());
+                    schema.insert("title".to_owned(), key.join(", ").into());
+                }
+                JsonSchemaGroup { key, schema }
+            })
+            .collect()
+    }
+
+    /// Convert this to a json summary
+    pub fn to_json(&self, options: &PrintOptions) -> String {
+        serde_json::to_string_pretty(&self.to_json_summary(options))
+            .expect("Failed to serialize json summary")
+    }
+
+    pub fn accumulate(&mut self, metadata: &LogLineMetadata<'_>) {
+        match metadata.filter {
+            LineFilter::Keep => {}
+            LineFilter::Skip | LineFilter::Stop => {
+                self.filtered_lines += 1;
+                self.filtered_bytes += metadata.bytes;
+                return;
+            }
+            LineFilter::Exclude => {
+                self.excluded_lines += 1;
+                self.excluded_bytes += metadata.bytes;
+                return;
+            }
+        }
+        match &mut self.top {
+            Some(top) => top.accumulate(&metadata.type_name, metadata.bytes),
+            // don't use entry API as that would require cloning for _every_
+            // lookup
+            None => match self.type_counts.get_mut(metadata.type_name.as_ref()) {
+                Some(stats) => {
+                    stats.add(metadata.bytes);
+                }
+                None => {
+                    self.type_counts
+                        .insert(metadata.type_name.to_vec(), TypeStats::new(metadata.bytes));
+                }
+            },
+        }
+        if let Some(samples) = &mut self.samples {
+            samples.accumulate(&metadata.type_name, metadata.line);
+        }
+        if let (Some(buckets), Some(timestamp)) = (&mut self.buckets, metadata.timestamp) {
+            buckets.accumulate(&metadata.type_name, metadata.bytes, timestamp);
+        }
+        if let Some(document) = &metadata.document {
+            match self.schemas.get_mut(metadata.type_name.as_ref()) {
+                Some(schema) => schema.observe(document),
+                None => {
+                    let mut schema = FieldSchema::default();
+                    schema.observe(document);
+                    self.schemas.insert(metadata.type_name.to_vec(), schema);
+                }
+            }
+        }
+        if let Some(value) = &metadata.distinct {
+            match self.distinct.get_mut(metadata.type_name.as_ref()) {
+                Some(sketch) => sketch.insert(value),
+                None => {
+                    let mut sketch = HyperLogLog::default();
+                    sketch.insert(value);
+                    self.distinct.insert(metadata.type_name.to_vec(), sketch);
+                }
+            }
+        }
+    }
+
+    /// Not quite [std::ops::Add]. This is an in-place merge with another [Summary]
+    pub fn combine(&mut self, mut other: Self) {
+        // once either side is bounded, so is the result
+        if self.top.is_none() {
+            if let Some(mut top) = other.top.take() {
+                for (key, stats) in self.type_counts.drain() {
+                    top.add_group(key, &stats);
+                }
+                self.top = Some(top);
+            }
+        }
+        match (&mut self.top, other.top) {
+            (Some(top), Some(other_top)) => top.combine(other_top),
+            (Some(top), None) => {
+                for (key, stats) in other.type_counts {
+                    top.add_group(key, &stats);
+                }
+            }
+            (None, _) => {
+                for (key, value) in other.type_counts {
+                    let entry = self.type_counts.entry(key).or_default();
+                    entry.combine(&value);
+                }
+            }
+        }
+        self.errors.combine(other.errors);
+        match (&mut self.buckets, other.buckets) {
+            (Some(buckets), Some(other_buckets)) => buckets.combine(other_buckets),
+            (None, other_buckets) => self.buckets = other_buckets,
+            (Some(_), None) => {}
+        }
+        self.filtered_lines += other.filtered_lines;
+        self.filtered_bytes += other.filtered_bytes;
+        self.excluded_lines += other.excluded_lines;
+        self.excluded_bytes += other.excluded_bytes;
+        match (&mut self.samples, other.samples) {
+            (Some(samples), Some(other_samples)) => samples.combine(other_samples),
+            (None, other_samples) => self.samples = other_samples,
+            (Some(_), None) => {}
+        }
+        for (key, schema) in other.schemas {
+            self.schemas.entry(key).or_default().combine(schema);
+        }
+        for (key, sketch) in other.distinct {
+            match self.distinct.get_mut(&key) {
+                Some(existing) => existing.combine(&sketch),
+                None => {
+                    self.distinct.insert(key, sketch);
+                }
+            }
+        }
+    }
+}
+
+/// This is just a convenience so we can abstract over handling different
+/// error types
+pub trait ErrorSummary: Debug + Default + Clone + Serialize + DeserializeOwned {
+    fn red_stderr_line<S: AsRef<str>>(&self, line: S) {
+        eprintln!("{}", line.as_ref().to_string().red());
+    }
+    fn display_error(&self);
+    /// Some indication of the error. E.g. 1 for 'an error' or `line_number`.
+    fn accumulate(&mut self, error: usize);
+    /// Combine another [ErrorSummary]
+    fn combine(&mut self, other: Self);
+    /// Total number of errors
+    fn total_errors(&self) -> usize;
+    /// Offset any line numbers by `first_line`, for errors counted from
+    /// part way through the input. Does nothing if lines aren't tracked.
+    fn shift(&mut self, _first_line: usize) {}
+    /// The line numbers which failed to parse, if they're tracked
+    fn error_lines(&self) -> Option<&[usize]> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[derive(Debug, Default, Clone, Serialize, Deserialize)]
+    pub(super) struct NoErrors;
+
+    impl ErrorSummary for NoErrors {
+        fn display_error(&self) {}
+        fn accumulate(&mut self, _: usize) {}
+        fn combine(&mut self, _: Self) {}
+        fn total_errors(&self) -> usize {
+            0
+        }
+    }
+
+    pub(super) fn summary(lines: &[(&[u8], usize)]) -> Summary<NoErrors> {
+        let mut summary = Summary::new();
+        for (type_name, bytes) in lines {
+            summary.accumulate(&LogLineMetadata {
+                bytes: *bytes,
+                ..LogLineMetadata::new(Cow::Borrowed(type_name), &[])
+            });
+        }
+        summary
+    }
+
+    #[test]
+    fn test_combine_composite_keys() {
+        let mut a = summary(&[(b"x\x1f1", 10), (b"y\x1f1", 5)]);
+        a.combine(summary(&[(b"x\x1f1", 1), (b"x\x1f2", 2)]));
+        assert_eq!(a.type_counts[&b"x\x1f1"[..]].bytes, 11);
+        assert_eq!(a.type_counts[&b"x\x1f1"[..]].lines, 2);
+        assert_eq!(a.type_counts[&b"x\x1f2"[..]].bytes, 2);
+        assert_eq!(a.type_counts[&b"y\x1f1"[..]].max, 5);
+        assert_eq!(a.key_width(), 2);
+    }
+
+    #[test]
+    fn test_buckets_when_asked() {
+        let line = |summary: &mut Summary<NoErrors>| {
+            summary.accumulate(&LogLineMetadata {
+                timestamp: Some(60_000),
+                ..LogLineMetadata::new(Cow::Borrowed(b"x"), b"{}")
+            })
+        };
+        let mut plain = Summary::new();
+        line(&mut plain);
+        assert!(plain.buckets.is_none());
+        let mut bucketed = Summary::new().with_buckets(true);
+        line(&mut bucketed);
+        plain.combine(bucketed);
+        let options = PrintOptions {
+            bucket: Some(TimeBucket::Minute),
+            ..PrintOptions::default()
+        };
+        assert_eq!(plain.to_json_summary(&options).buckets.len(), 1);
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let summary = summary(&[(b"x\x1f1", 10), (b"\xff", 5), (b"x\x1f1", 3), (b"", 0)]);
+        let json = serde_json::to_string(&summary).unwrap();
+        assert!(json.contains("\"x\\u001f1\""));
+        let restored: Summary<NoErrors> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.type_counts, summary.type_counts);
+    }
+
+    #[test]
+    fn test_nested_groups() {
+        let summary = summary(&[(b"x\x1f1", 10), (b"x\x1f2", 2), (b"y\x1f1", 5)]);
+        let groups = summary.nested_groups().groups;
+        assert_eq!(groups["x"].stats.bytes, 12);
+        assert_eq!(groups["x"].stats.min, 2);
+        assert_eq!(groups["x"].groups["2"].stats.bytes, 2);
+        assert_eq!(groups["y"].stats.lines, 1);
+        let table = summary
+            .to_table(&PrintOptions {
+                layout: GroupLayout::Nested,
+                ..PrintOptions::default()
+            })
+            .unwrap();
+        assert!(table.starts_with("Type"));
+        assert!(table.contains("\nx"));
+        assert!(table.contains("\n  1"));
+    }
+
+    #[test]
+    fn test_line_stats() {
+        let mut summary = summary(&[(b"x", 10), (b"x", 20), (b"y", 5)]);
+        summary.combine(self::summary(&[(b"x", 3)]));
+        let json = summary.to_json_summary(&PrintOptions::default());
+        assert_eq!(json.total_size, 38);
+        let x = &json.type_stats["x"];
+        assert_eq!((x.size, x.lines), (33, 3));
+        assert_eq!((x.min_size, x.max_size, x.mean_size), (3, 20, 11.0));
+        assert_eq!((x.p50_size, x.p99_size), (10, 20));
+        let table = summary.to_string();
+        assert!(table.starts_with("Type"));
+        assert!(table.lines().nth(1).unwrap().starts_with("x"));
+    }
+
+    #[test]
+    fn test_excluded_lines() {
+        let mut summary = summary(&[(b"x", 10)]);
+        for (filter, bytes) in [(LineFilter::Exclude, 4), (LineFilter::Skip, 2)] {
+            summary.accumulate(&LogLineMetadata {
+                bytes,
+                filter,
+                ..LogLineMetadata::new(Cow::Borrowed(b"y"), &[])
+            });
+        }
+        summary.combine(summary.clone());
+        let json = summary.to_json_summary(&PrintOptions::default());
+        assert_eq!(json.total_size, 20);
+        assert_eq!((json.excluded_lines, json.excluded_size), (2, 8));
+        assert_eq!((json.filtered_lines, json.filtered_size), (2, 4));
+        assert!(summary.to_string().contains("\nExcluded:  8 B"));
+    }
+
+    #[test]
+    fn test_distinct_values() {
+        let line = |user: &'static [u8]| LogLineMetadata {
+            distinct: Some(Cow::Borrowed(user)),
+            ..LogLineMetadata::new(Cow::Borrowed(b"x"), b"x")
+        };
+        let mut a = summary(&[]);
+        a.accumulate(&line(b"u1"));
+        a.accumulate(&line(b"u2"));
+        let mut b = summary(&[]);
+        b.accumulate(&line(b"u2"));
+        b.accumulate(&line(b"u3"));
+        let json = serde_json::to_string(&b).unwrap();
+        a.combine(serde_json::from_str(&json).unwrap());
+        let distinct = a.to_json_summary(&PrintOptions::default()).distinct;
+        assert_eq!(distinct.len(), 1);
+        assert_eq!((distinct[0].estimate, distinct[0].standard_error), (3, 0));
+    }
+
+    #[test]
+    fn test_top_keys() {
+        let mut summary = Summary::with_top_k(Some(TopK::new(1, TopBy::Lines)));
+        summary.accumulate(&LogLineMetadata {
+            bytes: 10,
+            ..LogLineMetadata::new(Cow::Borrowed(b"x"), &[])
+        });
+        // an unbounded summary is folded in to the bounded one
+        let mut total = self::summary(&[(b"x", 1), (b"y", 2)]);
+        total.combine(summary);
+        assert!(total.type_counts.is_empty());
+        let json = total.to_json_summary(&PrintOptions::default());
+        assert_eq!(json.total_size, 13);
+        let top = json.top_keys.unwrap();
+        assert_eq!(top.keys.len(), 1);
+        assert_eq!(top.keys[0].key, vec!["x"]);
+        assert!(top.keys[0].lower <= 2 && 2 <= top.keys[0].upper);
+        assert_eq!(
+            total.to_string(),
+            "Type    Lines  Up to\nx       2      2\nTotal:  3"
+        );
+    }
+
+    #[test]
+    fn test_top_keys_with_buckets() {
+        let mut summary: Summary<NoErrors> = Summary::with_top_k(Some(TopK::new(1, TopBy::Lines)))
+            .with_buckets(true)
+            .with_samples(Some(Sampling::new(1, 0)));
+        summary.accumulate(&LogLineMetadata {
+            timestamp: Some(60_000),
+            ..LogLineMetadata::new(Cow::Borrowed(b"x"), b"{}")
+        });
+        let options = PrintOptions {
+            bucket: Some(TimeBucket::Minute),
+            ..PrintOptions::default()
+        };
+        let json = summary.to_json_summary(&options);
+        assert_eq!(json.top_keys.unwrap().keys.len(), 1);
+        assert_eq!(json.buckets.len(), 1);
+        assert_eq!(json.samples[0].lines, vec!["{}"]);
+    }
+}
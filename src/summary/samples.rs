@@ -0,0 +1,227 @@
+use crate::summary::{deserialize_keyed, serialize_keyed, split_key, SerializedKey};
+use itertools::Itertools;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+
+/// How many example lines to keep per group, and how to choose them
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Sampling {
+    pub samples: usize,
+    /// The same seed picks the same lines from the same input
+    pub seed: u64,
+}
+
+impl Sampling {
+    pub fn new(samples: usize, seed: u64) -> Self {
+        Self { samples, seed }
+    }
+
+    /// A seed of its own for each part of the input summarized separately,
+    /// e.g. a file or a batch, so their choices are independent. Mixed
+    /// with FNV-1a and SplitMix64 rather than std's hashers, which may
+    /// change between releases, so a seed always picks the same lines.
+    pub fn for_part(&self, part: &[u8]) -> Self {
+        let mut hash = 0xcbf2_9ce4_8422_2325;
+        for byte in self.seed.to_le_bytes().iter().chain(part) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        Self {
+            samples: self.samples,
+            seed: next_random(&mut hash),
+        }
+    }
+}
+
+/// The next number from a SplitMix64 generator
+fn next_random(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonSamples {
+    /// The value of each field in the group key
+    pub key: Vec<String>,
+    /// In no particular order
+    pub lines: Vec<String>,
+}
+
+/// Example lines for each group. Every line is given a random priority
+/// and each group keeps the lines with the lowest, which is a uniform
+/// sample of the group however the input was split up, and merges by
+/// keeping the lowest of both.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct Samples {
+    sampling: Sampling,
+    /// The state of the random number generator
+    state: u64,
+    #[serde(
+        serialize_with = "serialize_keyed",
+        deserialize_with = "deserialize_keyed"
+    )]
+    groups: HashMap<Vec<u8>, Reservoir>,
+}
+
+impl Samples {
+    pub(super) fn new(sampling: Sampling) -> Self {
+        Self {
+            sampling,
+            state: sampling.seed,
+            groups: HashMap::new(),
+        }
+    }
+
+    pub(super) fn accumulate(&mut self, type_name: &[u8], line: &[u8]) {
+        let priority = next_random(&mut self.state);
+        let capacity = self.sampling.samples;
+        match self.groups.get_mut(type_name) {
+            Some(reservoir) => reservoir.offer(priority, line, capacity),
+            None => {
+                let mut reservoir = Reservoir::default();
+                reservoir.offer(priority, line, capacity);
+                self.groups.insert(type_name.to_vec(), reservoir);
+            }
+        }
+    }
+
+    /// Merge in another's groups, keeping the fewer samples if they differ
+    pub(super) fn combine(&mut self, other: Self) {
+        self.sampling.samples = self.sampling.samples.min(other.sampling.samples);
+        for (key, reservoir) in other.groups {
+            self.groups
+                .entry(key)
+                .or_default()
+                .combine(reservoir, self.sampling.samples);
+        }
+    }
+
+    /// Sorted by key
+    pub(super) fn to_json(&self) -> Vec<JsonSamples> {
+        self.groups
+            .iter()
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(key, reservoir)| JsonSamples {
+                key: split_key(key),
+                lines: reservoir
+                    .lines
+                    .iter()
+                    .map(|(_, line)| String::from_utf8_lossy(trim_line(line)).into_owned())
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// Drop the line ending, if it's still there
+fn trim_line(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// The lines of one group with the lowest priorities, sorted by priority
+#[derive(Debug, Clone, Default)]
+struct Reservoir {
+    lines: Vec<(u64, Vec<u8>)>,
+}
+
+impl Reservoir {
+    /// Only copies the line if it's kept
+    fn offer(&mut self, priority: u64, line: &[u8], capacity: usize) {
+        if self.lines.len() >= capacity
+            && !matches!(self.lines.last(), Some((highest, _)) if priority < *highest)
+        {
+            return;
+        }
+        let index = self.lines.partition_point(|(p, _)| *p < priority);
+        self.lines.insert(index, (priority, line.to_vec()));
+        self.lines.truncate(capacity);
+    }
+
+    fn combine(&mut self, other: Self, capacity: usize) {
+        self.lines.extend(other.lines);
+        self.lines.sort_by_key(|(priority, _)| *priority);
+        self.lines.truncate(capacity);
+    }
+}
+
+/// Written as a list of `[priority, line]` pairs, with the line readable
+/// where it can be
+impl Serialize for Reservoir {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            self.lines
+                .iter()
+                .map(|(priority, line)| (priority, SerializedKey::new(line))),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for Reservoir {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let lines: Vec<(u64, SerializedKey)> = Deserialize::deserialize(deserializer)?;
+        Ok(Reservoir {
+            lines: lines
+                .into_iter()
+                .map(|(priority, line)| (priority, line.into_bytes()))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_merge() {
+        let sampling = Sampling::new(2, 7);
+        let lines: Vec<String> = (0..100).map(|i| format!("line {}\n", i)).collect();
+        // the same lines split in two parts, or not at all
+        let mut whole = Samples::new(sampling);
+        let mut first = Samples::new(sampling.for_part(b"first"));
+        let mut second = Samples::new(sampling.for_part(b"second"));
+        for (i, line) in lines.iter().enumerate() {
+            whole.accumulate(b"x", line.as_bytes());
+            let part = if i < 50 { &mut first } else { &mut second };
+            part.accumulate(b"x", line.as_bytes());
+        }
+        first.combine(second);
+        for samples in [&whole, &first] {
+            let json = samples.to_json();
+            assert_eq!(json[0].lines.len(), 2);
+            assert!(json[0].lines.iter().all(|line| line.starts_with("line ")));
+        }
+        // seeded, so the choice is repeatable
+        let mut again = Samples::new(sampling);
+        for line in lines.iter() {
+            again.accumulate(b"x", line.as_bytes());
+        }
+        assert_eq!(again.to_json()[0].lines, whole.to_json()[0].lines);
+    }
+
+    #[test]
+    fn test_part_seeds_are_stable() {
+        // must never change, or the same --seed would pick different lines
+        let sampling = Sampling::new(2, 42);
+        assert_eq!(sampling.for_part(b"app.log").seed, 0xed8e_f478_7093_da95);
+        assert_ne!(sampling.for_part(b"a").seed, sampling.for_part(b"b").seed);
+    }
+
+    #[test]
+    fn test_uniform_samples() {
+        // each of 10 lines should be picked about as often as the others
+        let mut picked = [0; 10];
+        for seed in 0..2000 {
+            let mut samples = Samples::new(Sampling::new(1, seed));
+            for i in 0..10u8 {
+                samples.accumulate(b"x", &[i]);
+            }
+            picked[samples.groups[&b"x"[..]].lines[0].1[0] as usize] += 1;
+        }
+        assert!(picked.iter().all(|&n| n > 140 && n < 260), "{:?}", picked);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::summary::{ErrorSummary, Summary};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+/// The version of [SummaryFile] written by this build. Bump it whenever
+/// the format changes, and teach [SummaryFile::load] to read the old one.
+pub const SUMMARY_FORMAT_VERSION: u32 = 1;
+
+/// Parse errors in a saved summary. Line numbers are kept for each input,
+/// as they'd be meaningless once several inputs are combined.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedErrors {
+    /// All errors, including those without a line number
+    pub total: usize,
+    /// The lines which failed to parse in each input, where they're known.
+    /// These count from 0, but are shown counting from 1.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub lines: BTreeMap<String, Vec<usize>>,
+}
+
+impl ErrorSummary for SavedErrors {
+    fn display_error(&self) {
+        let mut located = 0;
+        for (source, lines) in self.lines.iter() {
+            self.red_stderr_line(format!("Failed to parse lines in {}:", source));
+            for line in lines.iter() {
+                self.red_stderr_line(format!("{}", line + 1));
+            }
+            located += lines.len();
+        }
+        if self.total > located {
+            self.red_stderr_line(format!(
+                "{} more line{} could not be parsed",
+                self.total - located,
+                if self.total - located == 1 { "" } else { "s" }
+            ));
+        }
+        if self.total > 0 {
+            self.red_stderr_line("");
+        }
+    }
+
+    /// An error without a line number
+    fn accumulate(&mut self, _: usize) {
+        self.total += 1;
+    }
+
+    fn combine(&mut self, other: Self) {
+        self.total += other.total;
+        for (source, lines) in other.lines {
+            self.lines.entry(source).or_default().extend(lines);
+        }
+    }
+
+    fn total_errors(&self) -> usize {
+        self.total
+    }
+}
+
+/// A summary saved to disk. Unlike [crate::JsonSummary] nothing is lost, so
+/// saved summaries can be merged in to one report later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SummaryFile {
+    pub version: u32,
+    pub summary: Summary<SavedErrors>,
+}
+
+impl SummaryFile {
+    pub fn new(summary: Summary<SavedErrors>) -> Self {
+        Self {
+            version: SUMMARY_FORMAT_VERSION,
+            summary,
+        }
+    }
+
+    /// Read a summary file written by this or any earlier version
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Summary<SavedErrors>> {
+        let value: serde_json::Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        let invalid = |reason: String| io::Error::new(io::ErrorKind::InvalidData, reason);
+        let version = value
+            .get("version")
+            .and_then(serde_json::Value::as_u64)
+            .ok_or_else(|| invalid("not a summary file, it has no version".to_owned()))?;
+        match version {
+            version if version == SUMMARY_FORMAT_VERSION as u64 => {
+                let file: SummaryFile = serde_json::from_value(value)?;
+                Ok(file.summary)
+            }
+            version if version > SUMMARY_FORMAT_VERSION as u64 => Err(invalid(format!(
+                "summary file version {} is newer than this build supports ({})",
+                version, SUMMARY_FORMAT_VERSION
+            ))),
+            version => Err(invalid(format!("unknown summary file version {}", version))),
+        }
+    }
+
+    /// Written alongside `path` and renamed over it, like
+    /// [crate::Checkpoint::save]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let writer = BufWriter::new(File::create(&temp)?);
+        serde_json::to_writer(writer, self)?;
+        std::fs::rename(&temp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LogLineMetadata;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_save_and_load() -> io::Result<()> {
+        let mut summary = Summary::<SavedErrors>::new();
+        summary.accumulate(&LogLineMetadata {
+            bytes: 10,
+            ..LogLineMetadata::new(Cow::Borrowed(b"a"), &[])
+        });
+        summary.errors_mut().combine(SavedErrors {
+            total: 3,
+            lines: BTreeMap::from([("app.log".to_owned(), vec![4, 9])]),
+        });
+        let path = std::env::temp_dir().join(format!("log_parser_saved_{}", std::process::id()));
+        SummaryFile::new(summary.clone()).save(&path)?;
+        let loaded = SummaryFile::load(&path)?;
+        assert_eq!(loaded.errors(), summary.errors());
+        assert_eq!(loaded.to_string(), summary.to_string());
+        let mut temp = path.clone().into_os_string();
+        temp.push(".tmp");
+        assert!(!Path::new(&temp).exists());
+
+        for version in [0, SUMMARY_FORMAT_VERSION + 1] {
+            let file = format!(r#"{{"version":{},"summary":{{}}}}"#, version);
+            std::fs::write(&path, file)?;
+            let err = SummaryFile::load(&path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        std::fs::remove_file(path)
+    }
+}
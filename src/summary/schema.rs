@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// The kinds of JSON value, in the order they're counted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueKind {
+    String,
+    Number,
+    Bool,
+    Null,
+    Object,
+    Array,
+}
+
+impl ValueKind {
+    const ALL: [ValueKind; 6] = [
+        ValueKind::String,
+        ValueKind::Number,
+        ValueKind::Bool,
+        ValueKind::Null,
+        ValueKind::Object,
+        ValueKind::Array,
+    ];
+
+    pub fn of(value: &Value) -> Self {
+        match value {
+            Value::String(_) => ValueKind::String,
+            Value::Number(_) => ValueKind::Number,
+            Value::Bool(_) => ValueKind::Bool,
+            Value::Null => ValueKind::Null,
+            Value::Object(_) => ValueKind::Object,
+            Value::Array(_) => ValueKind::Array,
+        }
+    }
+
+    /// As named by JSON Schema
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueKind::String => "string",
+            ValueKind::Number => "number",
+            ValueKind::Bool => "boolean",
+            ValueKind::Null => "null",
+            ValueKind::Object => "object",
+            ValueKind::Array => "array",
+        }
+    }
+}
+
+/// Everything seen at one place in a group's documents. Fields under
+/// objects and elements of arrays are kept below it, so two schemas merge
+/// by adding up each node.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FieldSchema {
+    /// How many values were seen here
+    pub count: usize,
+    /// How many values of each [ValueKind], in order
+    pub kinds: [usize; 6],
+    /// The fields of any objects seen here
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, FieldSchema>,
+    /// The elements of any arrays seen here
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<Box<FieldSchema>>,
+}
+
+/// A row of the table of fields, see [FieldSchema::rows]
+pub(super) struct FieldRow {
+    pub path: String,
+    pub kinds: Vec<&'static str>,
+    /// The share of the parent objects this field was in
+    pub presence: f64,
+}
+
+impl FieldSchema {
+    pub fn observe(&mut self, value: &Value) {
+        let kind = ValueKind::of(value);
+        self.count += 1;
+        self.kinds[kind as usize] += 1;
+        match value {
+            Value::Object(fields) => {
+                for (key, value) in fields {
+                    match self.properties.get_mut(key) {
+                        Some(field) => field.observe(value),
+                        None => {
+                            let mut field = FieldSchema::default();
+                            field.observe(value);
+                            self.properties.insert(key.clone(), field);
+                        }
+                    }
+                }
+            }
+            Value::Array(items) => {
+                let schema = self.items.get_or_insert_with(Box::default);
+                for item in items {
+                    schema.observe(item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn combine(&mut self, other: Self) {
+        self.count += other.count;
+        for (a, b) in self.kinds.iter_mut().zip(other.kinds.iter()) {
+            *a += b;
+        }
+        for (key, field) in other.properties {
+            self.properties.entry(key).or_default().combine(field);
+        }
+        if let Some(items) = other.items {
+            self.items.get_or_insert_with(Box::default).combine(*items);
+        }
+    }
+
+    /// The kinds of value seen here, in a fixed order
+    fn kinds(&self) -> impl Iterator<Item = ValueKind> + '_ {
+        ValueKind::ALL
+            .iter()
+            .copied()
+            .filter(move |kind| self.kinds[*kind as usize] > 0)
+    }
+
+    fn objects(&self) -> usize {
+        self.kinds[ValueKind::Object as usize]
+    }
+
+    /// As a JSON Schema. Fields seen in every object are required.
+    pub fn to_json_schema(&self) -> Value {
+        let mut schema = Map::new();
+        let kinds: Vec<_> = self.kinds().map(|kind| json!(kind.name())).collect();
+        match kinds.len() {
+            0 => {}
+            1 => {
+                schema.insert("type".to_owned(), kinds[0].clone());
+            }
+            _ => {
+                schema.insert("type".to_owned(), Value::Array(kinds));
+            }
+        }
+        if !self.properties.is_empty() {
+            let properties: Map<String, Value> = self
+                .properties
+                .iter()
+                .map(|(key, field)| (key.clone(), field.to_json_schema()))
+                .collect();
+            let required: Vec<Value> = self
+                .properties
+                .iter()
+                .filter(|(_, field)| field.count >= self.objects())
+                .map(|(key, _)| json!(key))
+                .collect();
+            schema.insert("properties".to_owned(), Value::Object(properties));
+            if !required.is_empty() {
+                schema.insert("required".to_owned(), Value::Array(required));
+            }
+        }
+        if let Some(items) = &self.items {
+            schema.insert("items".to_owned(), items.to_json_schema());
+        }
+        Value::Object(schema)
+    }
+
+    /// Every field below this one, depth first, with paths like `meta.tags[]`
+    pub(super) fn rows(&self) -> Vec<FieldRow> {
+        let mut rows = vec![];
+        self.push_rows("", &mut rows);
+        rows
+    }
+
+    fn push_rows(&self, path: &str, rows: &mut Vec<FieldRow>) {
+        for (key, field) in self.properties.iter() {
+            let path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            rows.push(FieldRow {
+                path: path.clone(),
+                kinds: field.kinds().map(|kind| kind.name()).collect(),
+                presence: field.count as f64 / self.objects() as f64,
+            });
+            field.push_rows(&path, rows);
+        }
+        if let Some(items) = &self.items {
+            items.push_rows(&format!("{}[]", path), rows);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_schema() {
+        let mut a = FieldSchema::default();
+        a.observe(&json!({"level": "info", "code": 200, "tags": ["x"]}));
+        let mut b = FieldSchema::default();
+        b.observe(&json!({"level": "error", "code": "E1", "meta": {"ok": null}}));
+        a.combine(b);
+        assert_eq!(a.count, 2);
+        assert_eq!(
+            a.to_json_schema(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "code": {"type": ["string", "number"]},
+                    "level": {"type": "string"},
+                    "meta": {"type": "object", "properties": {"ok": {"type": "null"}}, "required": ["ok"]},
+                    "tags": {"type": "array", "items": {"type": "string"}},
+                },
+                "required": ["code", "level"],
+            })
+        );
+        let rows: Vec<_> = a
+            .rows()
+            .into_iter()
+            .map(|row| (row.path, row.kinds.join(","), row.presence))
+            .collect();
+        assert_eq!(
+            rows[0],
+            ("code".to_owned(), "string,number".to_owned(), 1.0)
+        );
+        assert_eq!(rows[3], ("meta.ok".to_owned(), "null".to_owned(), 1.0));
+        assert_eq!(rows[4], ("tags".to_owned(), "array".to_owned(), 0.5));
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+/// The maximum relative error of a quantile estimate
+pub const SKETCH_RELATIVE_ACCURACY: f64 = 0.01;
+
+/// `ln(gamma)` where `gamma = (1 + a) / (1 - a)` for the relative accuracy
+/// `a` above. Float maths isn't allowed in a const.
+const LN_GAMMA: f64 = 0.020000666706669435;
+
+/// A DDSketch style histogram of line sizes. Sizes are counted in
+/// logarithmically sized buckets, so any quantile is within
+/// [SKETCH_RELATIVE_ACCURACY] of the true value. Merging two sketches just
+/// adds the bucket counts, so it's lossless regardless of how lines were
+/// split between mappers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SizeSketch {
+    /// The count of sizes in bucket `i`, which covers
+    /// `(gamma^(i - 1), gamma^i]`
+    buckets: Vec<usize>,
+    /// Sizes of zero don't have a logarithm
+    zeros: usize,
+}
+
+impl SizeSketch {
+    fn bucket(size: usize) -> usize {
+        ((size as f64).ln() / LN_GAMMA).ceil() as usize
+    }
+
+    /// The representative value of bucket `i`, chosen to minimise the
+    /// relative error for anything in it
+    fn value(bucket: usize) -> f64 {
+        2.0 * (bucket as f64 * LN_GAMMA).exp() / (LN_GAMMA.exp() + 1.0)
+    }
+
+    pub fn add(&mut self, size: usize) {
+        if size == 0 {
+            self.zeros += 1;
+            return;
+        }
+        let bucket = Self::bucket(size);
+        if bucket >= self.buckets.len() {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+    }
+
+    pub fn combine(&mut self, other: &Self) {
+        if other.buckets.len() > self.buckets.len() {
+            self.buckets.resize(other.buckets.len(), 0);
+        }
+        for (count, other) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *count += other;
+        }
+        self.zeros += other.zeros;
+    }
+
+    pub fn count(&self) -> usize {
+        self.zeros + self.buckets.iter().sum::<usize>()
+    }
+
+    /// Estimate the size at quantile `q` in `[0, 1]`. Returns `None` if the
+    /// sketch is empty.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * (count - 1) as f64).round() as usize;
+        if rank < self.zeros {
+            return Some(0.0);
+        }
+        let mut seen = self.zeros;
+        for (bucket, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen > rank {
+                return Some(Self::value(bucket));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(estimate: f64, actual: f64) {
+        let error = (estimate - actual).abs() / actual;
+        assert!(
+            error <= SKETCH_RELATIVE_ACCURACY,
+            "{} vs {}",
+            estimate,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_quantiles() {
+        let mut sketch = SizeSketch::default();
+        assert_eq!(sketch.quantile(0.5), None);
+        for size in 1..=1000 {
+            sketch.add(size);
+        }
+        assert_close(sketch.quantile(0.5).unwrap(), 500.0);
+        assert_close(sketch.quantile(0.9).unwrap(), 900.0);
+        assert_close(sketch.quantile(0.99).unwrap(), 990.0);
+        assert_close(sketch.quantile(1.0).unwrap(), 1000.0);
+    }
+
+    #[test]
+    fn test_combine_is_lossless() {
+        let mut whole = SizeSketch::default();
+        let mut left = SizeSketch::default();
+        let mut right = SizeSketch::default();
+        for size in (0..5000).map(|i| (i * 7919) % 100_000) {
+            whole.add(size);
+            if size % 3 == 0 {
+                left.add(size);
+            } else {
+                right.add(size);
+            }
+        }
+        left.combine(&right);
+        assert_eq!(left, whole);
+        assert_eq!(left.count(), 5000);
+    }
+}
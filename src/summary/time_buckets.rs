@@ -0,0 +1,239 @@
+use crate::parsers::format_timestamp;
+use crate::summary::{split_key, SerializedKey};
+use bytesize::ByteSize;
+use itertools::Itertools;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+/// How much time each row of a time-bucketed summary covers. Days start at
+/// midnight UTC.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeBucket {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl TimeBucket {
+    pub fn millis(&self) -> i64 {
+        match self {
+            TimeBucket::Minute => 60_000,
+            TimeBucket::Hour => 3_600_000,
+            TimeBucket::Day => 86_400_000,
+        }
+    }
+
+    /// The start of the bucket containing `timestamp`
+    pub fn start(&self, timestamp: i64) -> i64 {
+        timestamp - timestamp.rem_euclid(self.millis())
+    }
+}
+
+impl FromStr for TimeBucket {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minute" => Ok(TimeBucket::Minute),
+            "hour" => Ok(TimeBucket::Hour),
+            "day" => Ok(TimeBucket::Day),
+            _ => Err(format!(
+                "unknown bucket `{}`, expected minute, hour or day",
+                s
+            )),
+        }
+    }
+}
+
+/// The lines of one group in one bucket
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BucketStats {
+    pub bytes: usize,
+    pub lines: usize,
+}
+
+impl BucketStats {
+    fn add(&mut self, bytes: usize) {
+        self.bytes += bytes;
+        self.lines += 1;
+    }
+
+    fn combine(&mut self, other: &Self) {
+        self.bytes += other.bytes;
+        self.lines += other.lines;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonBucket {
+    /// The start of the bucket, in RFC 3339 form
+    pub start: String,
+    /// Sorted by size, largest first
+    pub groups: Vec<JsonBucketGroup>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonBucketGroup {
+    /// The value of each field in the group key
+    pub key: Vec<String>,
+    #[serde(flatten)]
+    pub stats: BucketStats,
+}
+
+/// Bytes and lines per group for every minute lines were seen in. Coarser
+/// buckets are added up from these when printing, so summaries can be
+/// combined without agreeing on a bucket size up front.
+#[derive(Debug, Clone, Default)]
+pub(super) struct TimeBuckets {
+    minutes: BTreeMap<i64, HashMap<Vec<u8>, BucketStats>>,
+}
+
+/// One bucket's groups, borrowing their keys
+type BucketGroups<'a> = HashMap<&'a [u8], BucketStats>;
+
+impl TimeBuckets {
+    pub(super) fn is_empty(&self) -> bool {
+        self.minutes.is_empty()
+    }
+
+    pub(super) fn accumulate(&mut self, type_name: &[u8], bytes: usize, timestamp: i64) {
+        let groups = self
+            .minutes
+            .entry(TimeBucket::Minute.start(timestamp))
+            .or_default();
+        match groups.get_mut(type_name) {
+            Some(stats) => stats.add(bytes),
+            None => {
+                let mut stats = BucketStats::default();
+                stats.add(bytes);
+                groups.insert(type_name.to_vec(), stats);
+            }
+        }
+    }
+
+    pub(super) fn combine(&mut self, other: Self) {
+        for (minute, groups) in other.minutes {
+            let entry = self.minutes.entry(minute).or_default();
+            for (key, stats) in groups {
+                entry.entry(key).or_default().combine(&stats);
+            }
+        }
+    }
+
+    /// Add up the minutes in to buckets of `width`, in time order
+    fn roll_up(&self, width: TimeBucket) -> BTreeMap<i64, BucketGroups<'_>> {
+        let mut buckets: BTreeMap<i64, BucketGroups<'_>> = BTreeMap::new();
+        for (minute, groups) in self.minutes.iter() {
+            let bucket = buckets.entry(width.start(*minute)).or_default();
+            for (key, stats) in groups.iter() {
+                bucket.entry(key.as_slice()).or_default().combine(stats);
+            }
+        }
+        buckets
+    }
+
+    pub(super) fn to_json(&self, width: TimeBucket) -> Vec<JsonBucket> {
+        self.roll_up(width)
+            .into_iter()
+            .map(|(start, groups)| JsonBucket {
+                start: format_timestamp(start),
+                groups: groups
+                    .into_iter()
+                    .sorted_by(|(_, a), (_, b)| b.bytes.cmp(&a.bytes))
+                    .map(|(key, stats)| JsonBucketGroup {
+                        key: split_key(key),
+                        stats,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// The rows of a table with the time, key, size and lines columns
+    pub(super) fn to_rows(&self, width: TimeBucket) -> Vec<String> {
+        let mut rows = vec![];
+        for (start, groups) in self.roll_up(width) {
+            let start = format_timestamp(start);
+            let sorted = groups
+                .into_iter()
+                .sorted_by(|(_, a), (_, b)| b.bytes.cmp(&a.bytes));
+            for (key, stats) in sorted {
+                rows.push(format!(
+                    "{}\t{}\t{}\t{}",
+                    start,
+                    split_key(key).join("\t"),
+                    ByteSize(stats.bytes as u64),
+                    stats.lines
+                ));
+            }
+        }
+        rows
+    }
+}
+
+/// Written as a list of `[minute, [[key, stats], ...]]`, like the type
+/// counts
+impl Serialize for TimeBuckets {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.minutes.iter().map(|(minute, groups)| {
+            let groups: Vec<_> = groups
+                .iter()
+                .map(|(k, v)| (SerializedKey::new(k), v))
+                .collect();
+            (minute, groups)
+        }))
+    }
+}
+
+impl<'de> Deserialize<'de> for TimeBuckets {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries: Vec<(i64, Vec<(SerializedKey, BucketStats)>)> =
+            Deserialize::deserialize(deserializer)?;
+        Ok(TimeBuckets {
+            minutes: entries
+                .into_iter()
+                .map(|(minute, groups)| {
+                    let groups = groups
+                        .into_iter()
+                        .map(|(k, v)| (k.into_bytes(), v))
+                        .collect();
+                    (minute, groups)
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roll_up() {
+        let hour = 3_600_000;
+        let mut a = TimeBuckets::default();
+        a.accumulate(b"x", 10, 1000);
+        a.accumulate(b"x", 20, 61_000);
+        let mut b = TimeBuckets::default();
+        b.accumulate(b"x", 5, 2000);
+        b.accumulate(b"y", 7, hour + 1);
+        a.combine(b);
+        assert_eq!(a.minutes.len(), 3);
+
+        let hours = a.to_json(TimeBucket::Hour);
+        assert_eq!(hours.len(), 2);
+        assert_eq!(hours[0].start, "1970-01-01T00:00:00Z");
+        assert_eq!(hours[0].groups[0].key, vec!["x"]);
+        assert_eq!(
+            hours[0].groups[0].stats,
+            BucketStats {
+                bytes: 35,
+                lines: 3
+            }
+        );
+        assert_eq!(hours[1].start, "1970-01-01T01:00:00Z");
+        assert_eq!(a.to_json(TimeBucket::Minute).len(), 3);
+        assert_eq!(TimeBucket::Day.start(-1), -86_400_000);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::summary::size_sketch::SizeSketch;
+use serde::{Deserialize, Serialize};
+
+/// The aggregated line sizes of a single group
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeStats {
+    /// The total size of all lines in bytes
+    pub bytes: usize,
+    /// The number of lines
+    pub lines: usize,
+    /// The smallest line in bytes
+    pub min: usize,
+    /// The largest line in bytes
+    pub max: usize,
+    /// The distribution of line sizes
+    pub sketch: SizeSketch,
+}
+
+/// An empty group. This is the identity for [TypeStats::combine].
+impl Default for TypeStats {
+    fn default() -> Self {
+        Self {
+            bytes: 0,
+            lines: 0,
+            min: usize::MAX,
+            max: 0,
+            sketch: SizeSketch::default(),
+        }
+    }
+}
+
+impl TypeStats {
+    /// A group containing a single line
+    pub fn new(bytes: usize) -> Self {
+        let mut stats = Self::default();
+        stats.add(bytes);
+        stats
+    }
+
+    pub fn add(&mut self, bytes: usize) {
+        self.bytes += bytes;
+        self.lines += 1;
+        self.min = self.min.min(bytes);
+        self.max = self.max.max(bytes);
+        self.sketch.add(bytes);
+    }
+
+    pub fn combine(&mut self, other: &Self) {
+        self.bytes += other.bytes;
+        self.lines += other.lines;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sketch.combine(&other.sketch);
+    }
+
+    /// The mean line size in bytes
+    pub fn mean(&self) -> f64 {
+        if self.lines == 0 {
+            0.0
+        } else {
+            self.bytes as f64 / self.lines as f64
+        }
+    }
+
+    /// Estimate the line size at quantile `q` in `[0, 1]`. The sketch is
+    /// only accurate to within a percent, so clamp to the exact bounds.
+    pub fn quantile(&self, q: f64) -> f64 {
+        match self.sketch.quantile(q) {
+            Some(size) => size.max(self.min as f64).min(self.max as f64),
+            None => 0.0,
+        }
+    }
+}
+
+/// The public facing JSON version of [TypeStats]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonTypeStats {
+    pub size: usize,
+    pub lines: usize,
+    pub min_size: usize,
+    pub max_size: usize,
+    pub mean_size: f64,
+    /// Estimated percentiles, see [crate::SKETCH_RELATIVE_ACCURACY]
+    pub p50_size: usize,
+    pub p90_size: usize,
+    pub p99_size: usize,
+}
+
+impl From<&TypeStats> for JsonTypeStats {
+    fn from(stats: &TypeStats) -> Self {
+        Self {
+            size: stats.bytes,
+            lines: stats.lines,
+            min_size: if stats.lines == 0 { 0 } else { stats.min },
+            max_size: stats.max,
+            mean_size: stats.mean(),
+            p50_size: stats.quantile(0.5).round() as usize,
+            p90_size: stats.quantile(0.9).round() as usize,
+            p99_size: stats.quantile(0.99).round() as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combine() {
+        let mut a = TypeStats::new(10);
+        a.add(30);
+        let mut b = TypeStats::default();
+        b.combine(&TypeStats::new(5));
+        b.add(100);
+        a.combine(&b);
+        assert_eq!(a.bytes, 145);
+        assert_eq!(a.lines, 4);
+        assert_eq!(a.min, 5);
+        assert_eq!(a.max, 100);
+        assert_eq!(a.mean(), 36.25);
+        assert_eq!(a.sketch.count(), 4);
+        assert_eq!(a.quantile(0.0).round(), 5.0);
+        assert_eq!(a.quantile(1.0).round(), 100.0);
+    }
+
+    #[test]
+    fn test_empty_json() {
+        let json = JsonTypeStats::from(&TypeStats::default());
+        assert_eq!(json.min_size, 0);
+        assert_eq!(json.mean_size, 0.0);
+    }
+}